@@ -0,0 +1,107 @@
+// 每个 ticker 重新计算关联三角组合的耗时: 原 HashMap + 拷贝 TriPair 的实现与下标索引的实现对比
+// cargo bench --bench scan
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use triangle::config::Configuration;
+use triangle::trading_pair::TradingPair;
+use triangle::tri_pair::{derive_tri_pairs, to_tri_angle_symbol, Profit, Ticker, TriPair};
+use triangle::triangle::TriAngleArb;
+
+const COINS: usize = 200;
+
+// COINS 个币种, 每个币种都有 /BTC /USDT /ETH 交易对, 再加上三个桥交易对
+fn market() -> (Vec<TradingPair>, Vec<Ticker>) {
+    let mut pairs = Vec::new();
+    let mut tickers = Vec::new();
+    let mut add = |base: String, quote: &str, price: f64| {
+        let idx = pairs.len() as u32 + 1;
+        pairs.push(TradingPair::new(idx, format!("{}{}", base, quote), base, quote.to_string(), 0.001, 0.999));
+        tickers.push(Ticker { idx, ba: [price * 1.0005, 10.0], bb: [price * 0.9995, 10.0], ..Ticker::default() });
+    };
+    add("BTC".to_string(), "USDT", 40000.0);
+    add("ETH".to_string(), "BTC", 0.05);
+    add("ETH".to_string(), "USDT", 2000.0);
+    for i in 0..COINS {
+        let usdt = 1.0 + i as f64;
+        add(format!("C{}", i), "USDT", usdt);
+        add(format!("C{}", i), "BTC", usdt / 40000.0);
+        add(format!("C{}", i), "ETH", usdt / 2000.0);
+    }
+    (pairs, tickers)
+}
+
+// 原实现: 交易对 -> 拷贝的 TriPair 数组, 每个组合三次 HashMap 查找 (原来 ticker 保存在 TradingPair.tick 中)
+struct Legacy {
+    id_tickers: HashMap<u32, Ticker>,
+    angles: HashMap<u32, Vec<TriPair>>,
+}
+
+impl Legacy {
+    // 与原 on_ticker 相同, 收益换算为 report_currency 后比较
+    fn on_ticker(&mut self, ta: &TriAngleArb, tick: Ticker) -> Profit {
+        let idx = tick.idx;
+        *self.id_tickers.get_mut(&idx).unwrap() = tick;
+        let mut best = Profit::default();
+        if let Some(angles) = self.angles.get(&idx) {
+            for tp in angles {
+                let t0 = self.id_tickers.get(&tp.pairs[0]).unwrap();
+                let t1 = self.id_tickers.get(&tp.pairs[1]).unwrap();
+                let t2 = self.id_tickers.get(&tp.pairs[2]).unwrap();
+                let mut profit = tp.calc_profit(t0, t1, t2, false);
+                if profit.profit != 0.0 {
+                    profit.value = ta.convert(profit.profit, &profit.asset, ta.report_currency()).unwrap_or(0.0);
+                }
+                if profit.value > best.value {
+                    best = profit;
+                }
+            }
+        }
+        best
+    }
+}
+
+fn bench_scan(c: &mut Criterion) {
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.base_quotes = Some(vec!["BTC".to_string(), "USDT".to_string(), "ETH".to_string()]);
+    cfg.exclude_coins = None;
+    let (pairs, tickers) = market();
+
+    let map = derive_tri_pairs(&pairs, cfg.base_quotes.as_ref().unwrap(), None, None);
+    let mut legacy = Legacy {
+        id_tickers: tickers.iter().map(|t| (t.idx, t.clone())).collect(),
+        angles: to_tri_angle_symbol(&map),
+    };
+    let mut ta = TriAngleArb::with_pairs(&cfg, pairs);
+    for tick in &tickers {
+        ta.set_ticker(tick.clone());
+    }
+
+    // C0/BTC 只关联少数几个组合, BTC/USDT 作为桥关联所有币种
+    for (name, idx) in [("coin", 5usize), ("bridge", 1usize)].iter() {
+        let tick = tickers[*idx - 1].clone();
+        let n = ta.angle_ids(tick.idx).len();
+        c.bench_function(&format!("legacy {} tick ({} triangles)", name, n), |b| {
+            b.iter(|| black_box(legacy.on_ticker(&ta, tick.clone())))
+        });
+        c.bench_function(&format!("indexed {} tick ({} triangles)", name, n), |b| {
+            b.iter(|| {
+                ta.set_ticker(tick.clone());
+                black_box(ta.on_symbol(tick.idx))
+            })
+        });
+        // 扫描循环的路径: 每个组合的收益写入复用的 Vec
+        let mut profits: Vec<Profit> = Vec::new();
+        c.bench_function(&format!("indexed {} tick, all profits ({} triangles)", name, n), |b| {
+            b.iter(|| {
+                ta.set_ticker(tick.clone());
+                ta.on_symbol_into(tick.idx, 0, &mut profits);
+                black_box(profits.len())
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bench_scan);
+criterion_main!(benches);
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::config::Configuration;
+use crate::simulate::simulate;
+use crate::tracker::{Opportunity, OpportunityTracker};
+use crate::trading_pair::TradingPair;
+use crate::tri_pair::{Profit, Ticker, TriPair};
+use crate::triangle::TriAngleArb;
+
+// 单个三角套利组合的回测统计
+#[derive(Debug, Default, Clone)]
+pub struct TriStats {
+    pub opportunities: u64,        // 超过阈值的机会次数, 机会持续期间只算一次
+    pub hits: u64,                 // 按步长取整、扣除手续费后仍盈利的次数
+    pub pnl: HashMap<String, f64>, // 模拟成交的理论收益, 按起始资产分别累计
+    pub lifetime_ms: i64,          // 所有机会持续时间之和
+}
+
+impl TriStats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.opportunities == 0 {
+            return 0.0
+        }
+        self.hits as f64 / self.opportunities as f64
+    }
+
+    pub fn avg_lifetime_ms(&self) -> f64 {
+        if self.opportunities == 0 {
+            return 0.0
+        }
+        self.lifetime_ms as f64 / self.opportunities as f64
+    }
+
+    fn merge(&mut self, other: &TriStats) {
+        self.opportunities += other.opportunities;
+        self.hits += other.hits;
+        self.lifetime_ms += other.lifetime_ms;
+        for (asset, pnl) in &other.pnl {
+            *self.pnl.entry(asset.clone()).or_insert(0.0) += pnl;
+        }
+    }
+}
+
+fn fmt_pnl(pnl: &HashMap<String, f64>) -> String {
+    let mut assets: Vec<&String> = pnl.keys().collect();
+    assets.sort();
+    assets.iter().map(|a| format!("{:.8} {}", pnl[*a], a)).collect::<Vec<String>>().join(", ")
+}
+
+// 回测报告, triangles 按名称排序
+#[derive(Debug, Default, Clone)]
+pub struct Report {
+    pub triangles: Vec<(String, TriStats)>,
+    pub total: TriStats,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<24} {:>8} {:>8} {:>8} {:>14}  pnl", "triangle", "count", "hits", "hit%", "avg life ms")?;
+        for (name, st) in &self.triangles {
+            writeln!(f, "{:<24} {:>8} {:>8} {:>8.2} {:>14.1}  {}",
+                name, st.opportunities, st.hits, st.hit_rate() * 100.0, st.avg_lifetime_ms(), fmt_pnl(&st.pnl))?;
+        }
+        write!(f, "{:<24} {:>8} {:>8} {:>8.2} {:>14.1}  {}",
+            "total", self.total.opportunities, self.total.hits, self.total.hit_rate() * 100.0,
+            self.total.avg_lifetime_ms(), fmt_pnl(&self.total.pnl))
+    }
+}
+
+// 回测: 用录制的 ticker 驱动 TriAngleArb, 对每个超过 trading_profit_threshold 的机会
+// 在机会出现时按盘口模拟成交一次, 统计机会次数、收益、命中率和持续时间
+pub struct Backtest {
+    ta: TriAngleArb,
+    symbol_idx: HashMap<String, u32>,
+    tracker: OpportunityTracker,
+    stats: HashMap<String, TriStats>,
+}
+
+impl Backtest {
+    pub fn new(config: &Configuration, pairs: Vec<TradingPair>) -> Backtest {
+        let symbol_idx = pairs.iter().map(|x| (x.get_symbol(), x.get_symbol_idx())).collect();
+        Backtest {
+            ta: TriAngleArb::with_pairs(config, pairs),
+            symbol_idx,
+            tracker: OpportunityTracker::new(config.trading_profit_threshold / 100.0),
+            stats: HashMap::new(),
+        }
+    }
+
+    // 处理一条录制的 ticker, ts 为接收时间 (ms)
+    pub fn on_record(&mut self, ts: i64, symbol: &str, mut tick: Ticker) {
+        let idx = match self.symbol_idx.get(symbol) {
+            Some(idx) => *idx,
+            None => return,
+        };
+        tick.idx = idx;
+        self.ta.set_ticker(tick);
+
+        let ids: Vec<usize> = self.ta.angle_ids(idx).to_vec();
+        let profits = self.ta.on_symbol_all(idx);
+        for (id, profit) in ids.into_iter().zip(profits) {
+            let tp = self.ta.triangle(id).clone();
+            self.on_profit(ts, &tp, profit);
+        }
+    }
+
+    fn on_profit(&mut self, ts: i64, tp: &TriPair, profit: Profit) {
+        // ticker 未初始化时没有结果, 不影响已有的机会
+        if profit.name.is_empty() {
+            return
+        }
+        let update = self.tracker.update(&tp.name, &profit, ts);
+        for op in &update.closed {
+            self.close(op);
+        }
+        // 机会持续期间只在出现时模拟成交一次
+        if !update.opened {
+            return
+        }
+
+        let pairs = [
+            self.ta.get_pair(tp.pairs[0]).unwrap(),
+            self.ta.get_pair(tp.pairs[1]).unwrap(),
+            self.ta.get_pair(tp.pairs[2]).unwrap(),
+        ];
+        let exec = simulate(tp, &profit, pairs);
+        let st = self.stats.entry(tp.name.clone()).or_default();
+        st.opportunities += 1;
+        if let Some(exec) = exec {
+            if exec.pnl() > 0.0 {
+                st.hits += 1;
+            }
+            *st.pnl.entry(exec.asset.clone()).or_insert(0.0) += exec.pnl();
+        }
+    }
+
+    fn close(&mut self, op: &Opportunity) {
+        let st = self.stats.entry(op.name.clone()).or_default();
+        st.lifetime_ms += op.duration_ms();
+    }
+
+    // 结束回测, 仍在持续的机会以最后一次出现的时间关闭
+    pub fn finish(&mut self) -> Report {
+        for op in self.tracker.finish() {
+            self.close(&op);
+        }
+
+        let mut triangles: Vec<(String, TriStats)> = self.stats.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        triangles.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut total = TriStats::default();
+        for (_, st) in &triangles {
+            total.merge(st);
+        }
+
+        Report {
+            triangles,
+            total,
+        }
+    }
+}
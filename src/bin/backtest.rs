@@ -0,0 +1,50 @@
+use std::env;
+use std::process;
+use triangle::backtest::Backtest;
+use triangle::config::{split_config_args, Configuration};
+use triangle::logger;
+use triangle::recorder::TickerReplay;
+
+// backtest <config> [--config <file>]... <record file>...
+// 按顺序回放录制文件, 交易对取自第一个文件
+fn main() {
+  let args: Vec<String> = env::args().collect();
+  let (paths, files) = match split_config_args(args.get(1..).unwrap_or_default()) {
+    Ok((paths, files)) if !files.is_empty() => (paths, files),
+    _ => {
+      eprintln!("usage: backtest <config> [--config <file>]... <record file>...");
+      process::exit(2);
+    }
+  };
+  let paths: Vec<&str> = paths.iter().map(|x| x.as_str()).collect();
+  let config = match Configuration::from_paths(&paths) {
+    Ok(config) => config,
+    Err(e) => {
+      eprintln!("{}", e);
+      process::exit(1);
+    }
+  };
+  if let Err(e) = logger::init(&config) {
+    eprintln!("{}", e);
+    process::exit(1);
+  }
+  let mut bt: Option<Backtest> = None;
+
+  for path in &files {
+    let mut replay = match TickerReplay::open(path, 0.0) {
+      Ok(replay) => replay,
+      Err(e) => {
+        eprintln!("{}: {}", path, e);
+        process::exit(1);
+      }
+    };
+    let bt = bt.get_or_insert_with(|| Backtest::new(&config, replay.pairs(&config)));
+    for (ts, symbol, tick) in replay.records() {
+      bt.on_record(ts, &symbol, tick);
+    }
+  }
+
+  if let Some(mut bt) = bt {
+    println!("{}", bt.finish());
+  }
+}
@@ -0,0 +1,116 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use futures::StreamExt;
+use log::{error, warn, LevelFilter};
+use telegram_bot::{Api, MessageKind, UpdateKind, UserId};
+
+use crate::config::Configuration;
+
+// 轮询出错后重试的间隔
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// Telegram 命令, 只接受 telegram_user_id 发送的消息
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Status,          // /status: 订阅的交易对、三角组合数量, 最后一个 ticker 距今的时间
+    Top(usize),      // /top [n]: 当前收益最优的 n 个三角组合, 默认 5
+    Pause,           // /pause: 暂停交易, 继续扫描
+    Resume,          // /resume: 恢复交易
+    Threshold(f64),  // /threshold <x>: 修改 trading_profit_threshold (百分比)
+    LogLevel(LevelFilter), // /loglevel <level>: 修改日志级别, 例如 debug 时输出 calc_profit 的计算过程
+    Help,            // /help 以及无法识别的命令
+}
+
+// /top 默认显示的数量
+pub const DEFAULT_TOP: usize = 5;
+
+impl Command {
+    // 解析一条消息, 不是命令 (不以 / 开头) 时返回 None; 支持 /cmd@botname 的形式
+    pub fn parse(text: &str) -> Option<Command> {
+        let mut words = text.split_whitespace();
+        let cmd = words.next()?.strip_prefix('/')?;
+        let cmd = cmd.split('@').next().unwrap_or(cmd).to_lowercase();
+        let arg = words.next();
+        let command = match cmd.as_str() {
+            "status" => Command::Status,
+            "top" => match arg.map(|x| x.parse::<usize>()) {
+                None => Command::Top(DEFAULT_TOP),
+                Some(Ok(n)) if n > 0 => Command::Top(n),
+                Some(_) => Command::Help,
+            },
+            "pause" => Command::Pause,
+            "resume" => Command::Resume,
+            "threshold" => match arg.and_then(|x| x.parse::<f64>().ok()) {
+                Some(x) if x.is_finite() => Command::Threshold(x),
+                _ => Command::Help,
+            },
+            "loglevel" => match arg.and_then(|x| x.parse::<LevelFilter>().ok()) {
+                Some(level) => Command::LogLevel(level),
+                None => Command::Help,
+            },
+            _ => Command::Help,
+        };
+        Some(command)
+    }
+}
+
+pub const HELP: &str = "/status - pairs, triangles and last tick age
+/top [n] - current best opportunities
+/pause - pause trading
+/resume - resume trading
+/threshold <percent> - set trading_profit_threshold
+/loglevel <error|warn|info|debug|trace> - set log level";
+
+// telegram_token 和 telegram_user_id 都设置时启动命令监听, 收到的命令通过队列发送给扫描循环
+pub fn listen(config: &Configuration) -> Option<Receiver<Command>> {
+    if config.telegram_token.is_empty() || config.telegram_user_id == 0 {
+        return None
+    }
+    let (tx, rx): (Sender<Command>, Receiver<Command>) = mpsc::channel();
+    let token = config.telegram_token.clone();
+    let user_id = config.telegram_user_id;
+    thread::spawn(move || listen_loop(&token, user_id, tx));
+    Some(rx)
+}
+
+// 通过 getUpdates 长轮询接收消息, 扫描循环退出 (队列断开) 时结束
+fn listen_loop(token: &str, user_id: i64, tx: Sender<Command>) {
+    let mut rt = match tokio::runtime::Builder::new().basic_scheduler().enable_all().build() {
+        Ok(rt) => rt,
+        Err(e) => {
+            error!(error:% = e; "telegram commands disabled");
+            return
+        }
+    };
+    let api = Api::new(token);
+    let user = UserId::new(user_id);
+    let mut stream = api.stream();
+
+    while let Some(update) = rt.block_on(stream.next()) {
+        let update = match update {
+            Ok(update) => update,
+            Err(e) => {
+                warn!(error:% = e; "telegram get updates failed");
+                thread::sleep(RETRY_DELAY);
+                continue
+            }
+        };
+        let message = match update.kind {
+            UpdateKind::Message(message) => message,
+            _ => continue,
+        };
+        if message.from.id != user {
+            warn!(user:% = message.from.id; "telegram command from unknown user ignored");
+            continue
+        }
+        if let MessageKind::Text { data, .. } = message.kind {
+            if let Some(cmd) = Command::parse(&data) {
+                if tx.send(cmd).is_err() {
+                    return
+                }
+            }
+        }
+    }
+}
@@ -1,13 +1,38 @@
 use serde::{Deserialize, Serialize};
-use std::error::Error;
+use std::collections::HashMap;
+use std::env;
 use std::fmt;
-use std::fs::File;
-use std::io::BufReader;
+use std::fs;
 use std::path::Path;
 
+use serde_json::Value;
+
+use log::LevelFilter;
+
+use crate::error::{Error, Result};
+
+// 手续费表, 费率均为百分比, 例如 0.1 表示 0.1%
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct FeeSchedule {
+  // VIP 等级, 作为 vip_taker_fees 的下标
+  #[serde(default)]
+  pub vip_level: Option<usize>,
+  #[serde(default)]
+  pub vip_taker_fees: Option<Vec<f64>>,
+  // 使用 BNB 抵扣手续费的折扣, 例如 25 表示手续费打 75 折
+  #[serde(default)]
+  pub bnb_discount: Option<f64>,
+  // 单个交易对的费率, 优先级最高且不参与 BNB 折扣, 例如零手续费活动 {"BTCUSDT": 0}
+  #[serde(default)]
+  pub symbols: Option<HashMap<String, f64>>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Configuration {
+  // 可以不写在配置文件中, 通过 TRIANGLE_API_KEY / TRIANGLE_API_SECRET 设置
+  #[serde(default)]
   pub api_key: String,
+  #[serde(default)]
   pub api_secret: String,
   pub investment_base: String,
   pub investment_min: f64,
@@ -18,14 +43,193 @@ pub struct Configuration {
   pub trading_taker_fee: f64,
   pub trading_profit_threshold: f64,
   pub trading_age_threshold: u64,
+  // depth_enabled 为 true 时订阅的深度档数 (5, 10 或 20)
   pub depth_size: i32,
 
+  // 所有交易对统一的手续费乘数, 例如 0.999; 不设置时使用 trading_taker_fee 和 fee_schedule
   #[serde(default)]
   pub fee: Option<f64>,
   #[serde(default)]
+  pub fee_schedule: Option<FeeSchedule>,
+  #[serde(default)]
   pub base_quotes: Option<Vec<String>>,
   #[serde(default)]
   pub exclude_coins: Option<Vec<String>>,
+  // 只扫描这些币种的三角组合, 不设置时不限制
+  #[serde(default)]
+  pub allow_coins: Option<Vec<String>>,
+  // 在所有交易对上枚举三角组合, 任意交易对都可以作为桥, 不再局限于 base_quotes; 不设置时为 false
+  #[serde(default)]
+  pub all_triangles: Option<bool>,
+  // 订阅 depth_size 档深度代替最优价格, 不设置时为 false
+  #[serde(default)]
+  pub depth_enabled: Option<bool>,
+  // 模拟交易, 不设置时为 true; 设置为 false 时使用 api_key/api_secret 实盘下单
+  #[serde(default)]
+  pub paper_trading: Option<bool>,
+  // 模拟交易的初始余额, 不设置时为 investment_max 个 investment_base
+  #[serde(default)]
+  pub paper_balances: Option<HashMap<String, f64>>,
+  // 收益统一换算的计价资产, 不设置时为 investment_base
+  #[serde(default)]
+  pub report_currency: Option<String>,
+  // N 腿循环套利的最大腿数 (3-5), 通过货币图搜索套利环; 不设置时只扫描三角套利
+  #[serde(default)]
+  pub cycle_max_legs: Option<usize>,
+  // Telegram 通知, token 为空或 user id 为 0 时不通知
+  #[serde(default)]
+  pub telegram_token: String,
+  #[serde(default)]
+  pub telegram_user_id: i64,
+  // 收益比例 (百分比) 达到该值的机会才通知, 不设置时为 trading_profit_threshold
+  #[serde(default)]
+  pub telegram_notify_ratio: Option<f64>,
+  // 每分钟最多发送的消息数量, 不设置时为 20
+  #[serde(default)]
+  pub telegram_rate_per_minute: Option<usize>,
+  // Prometheus /metrics 的监听地址, 例如 "127.0.0.1:9898"; 不设置时不提供
+  #[serde(default)]
+  pub metrics_addr: Option<String>,
+  // 机会写入的 SQLite 数据库文件, 不存在时创建; 不设置时不写入
+  #[serde(default)]
+  pub sqlite_path: Option<String>,
+  // 机会导出的目录, 不存在时创建; 不设置时不导出
+  #[serde(default)]
+  pub export_dir: Option<String>,
+  // 导出格式 csv 或 parquet (需要 parquet feature), 不设置时为 csv
+  #[serde(default)]
+  pub export_format: Option<String>,
+  // 文件超过该大小 (MB) 时换到新文件, 不设置时不按大小轮换
+  #[serde(default)]
+  pub export_max_mb: Option<f64>,
+  // 每小时换一个文件, 不设置时为 false
+  #[serde(default)]
+  pub export_hourly: Option<bool>,
+  // 日志格式 text 或 json, 不设置时为 text
+  #[serde(default)]
+  pub log_format: Option<String>,
+  // 日志级别 error/warn/info/debug/trace/off, 不设置时为 info; 运行时可通过 /loglevel 修改
+  #[serde(default)]
+  pub log_level: Option<String>,
+}
+
+// export_format 的取值, 在配置中解析, export 只负责写入
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+  Csv,
+  Parquet,
+}
+
+impl ExportFormat {
+  pub fn parse(s: &str) -> Result<ExportFormat> {
+    match s.to_lowercase().as_str() {
+      "csv" => Ok(ExportFormat::Csv),
+      "parquet" if cfg!(feature = "parquet") => Ok(ExportFormat::Parquet),
+      "parquet" => Err(Error::Config("export_format parquet requires the parquet feature".to_string())),
+      _ => Err(Error::Config(format!("unknown export_format: {}", s))),
+    }
+  }
+}
+
+// 日志输出格式: text 便于阅读, json 每行一个对象, 便于日志系统解析
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+  Text,
+  Json,
+}
+
+impl LogFormat {
+  pub fn parse(s: &str) -> Result<LogFormat> {
+    match s.to_lowercase().as_str() {
+      "text" => Ok(LogFormat::Text),
+      "json" => Ok(LogFormat::Json),
+      _ => Err(Error::Config(format!("unknown log_format: {}", s))),
+    }
+  }
+}
+
+pub fn parse_level(s: &str) -> Result<LevelFilter> {
+  s.parse().map_err(|_| Error::Config(format!("unknown log level: {}", s)))
+}
+
+// Configuration::validate 发现的问题, field 为配置中的字段名
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigIssue {
+  Missing { field: String },                                  // 必填的字段为空
+  NotPositive { field: String, value: f64 },                  // 必须大于 0
+  MinAboveMax { min: f64, max: f64 },                         // investment_min > investment_max
+  OutOfRange { field: String, value: f64, min: f64, max: f64 }, // 超出 [min, max]
+  Invalid { field: String, reason: String },                  // 无法使用的取值
+  Unusual { field: String, reason: String },                  // 可以运行, 但可能不是预期的取值
+}
+
+impl fmt::Display for ConfigIssue {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ConfigIssue::Missing { field } => write!(f, "{} must not be empty", field),
+      ConfigIssue::NotPositive { field, value } => write!(f, "{} must be positive, got {}", field, value),
+      ConfigIssue::MinAboveMax { min, max } => write!(f, "investment_min {} is greater than investment_max {}", min, max),
+      ConfigIssue::OutOfRange { field, value, min, max } => write!(f, "{} must be within [{}, {}], got {}", field, min, max, value),
+      ConfigIssue::Invalid { field, reason } | ConfigIssue::Unusual { field, reason } => write!(f, "{}: {}", field, reason),
+    }
+  }
+}
+
+// 配置检查的结果, 有 errors 时不能启动
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validation {
+  pub errors: Vec<ConfigIssue>,
+  pub warnings: Vec<ConfigIssue>,
+}
+
+impl Validation {
+  pub fn is_ok(&self) -> bool {
+    self.errors.is_empty()
+  }
+
+  fn error(&mut self, issue: ConfigIssue) {
+    self.errors.push(issue);
+  }
+
+  fn warn(&mut self, field: &str, reason: &str) {
+    self.warnings.push(ConfigIssue::Unusual { field: field.to_string(), reason: reason.to_string() });
+  }
+
+  fn invalid(&mut self, field: &str, reason: String) {
+    self.errors.push(ConfigIssue::Invalid { field: field.to_string(), reason });
+  }
+
+  fn positive(&mut self, field: &str, value: f64) {
+    if value.is_nan() || value <= 0.0 {
+      self.error(ConfigIssue::NotPositive { field: field.to_string(), value });
+    }
+  }
+
+  fn range(&mut self, field: &str, value: f64, min: f64, max: f64) {
+    if !(min..=max).contains(&value) {
+      self.error(ConfigIssue::OutOfRange { field: field.to_string(), value, min, max });
+    }
+  }
+}
+
+impl fmt::Display for Validation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for e in &self.errors {
+      writeln!(f, "error: {}", e)?;
+    }
+    for w in &self.warnings {
+      writeln!(f, "warning: {}", w)?;
+    }
+    write!(f, "{} errors, {} warnings", self.errors.len(), self.warnings.len())
+  }
+}
+
+// 配置错误的说明, 不带 "config error" 前缀
+fn reason(e: Error) -> String {
+  match e {
+    Error::Config(msg) => msg,
+    e => e.to_string(),
+  }
 }
 
 impl fmt::Display for Configuration {
@@ -34,19 +238,287 @@ impl fmt::Display for Configuration {
   }
 }
 
+// binance 深度数据的推送间隔 (<symbol>@depth<levels>@100ms), 毫秒; 最优价格只在变化时推送
+pub const STREAM_INTERVAL_MS: u64 = 100;
+
+// 覆盖配置字段的环境变量前缀, 例如 TRIANGLE_API_SECRET 覆盖 api_secret
+pub const ENV_PREFIX: &str = "TRIANGLE_";
+
 impl Configuration {
-  // Constructor
-  pub fn new(config_path: &str) -> Configuration {
-    match Configuration::parse_config_file(config_path) {
-      Ok(v) => v,
-      Err(e) => panic!("Failed to parse configuration file, error is {}", e),
+  // Constructor, 文件不存在或格式错误时返回错误; 之后应用 TRIANGLE_* 环境变量
+  pub fn new(config_path: &str) -> Result<Configuration> {
+    Configuration::from_paths(&[config_path])
+  }
+
+  // 按顺序叠加多个配置文件, 之后应用 TRIANGLE_* 环境变量; 路径原样使用, 可以包含逗号
+  pub fn from_paths(paths: &[&str]) -> Result<Configuration> {
+    Configuration::load(paths, env::vars())
+  }
+
+  // 按顺序读取并叠加配置文件 (json/toml/yaml, 按扩展名区分): 对象逐字段合并, 其他值整体覆盖
+  // 然后用 env 中 TRIANGLE_<字段名> 覆盖顶层字段: 字符串字段取原值, 其他字段按 JSON 解析, 例如
+  // TRIANGLE_TRADING_ENABLED=true, TRIANGLE_BASE_QUOTES='["BTC","USDT"]'
+  pub fn load<I: IntoIterator<Item = (String, String)>>(paths: &[&str], env: I) -> Result<Configuration> {
+    if paths.is_empty() {
+      return Err(Error::Config("no configuration file".to_string()))
+    }
+    let mut value = Value::Object(Default::default());
+    for path in paths {
+      merge(&mut value, read_config_file(path)?);
+    }
+    apply_env(&mut value, env)?;
+    let source = paths.join(" ");
+    serde_json::from_value(value).map_err(|e| Error::Config(format!("{}: {}", source, e)))
+  }
+
+  // 交易对的手续费乘数, 成交数量乘以该值即为扣除手续费后的数量
+  pub fn pair_fee(&self, symbol: &str) -> f64 {
+    let schedule = match &self.fee_schedule {
+      Some(schedule) => schedule,
+      None => return self.fee.unwrap_or(1.0 - self.trading_taker_fee / 100.0),
+    };
+    if let Some(fee) = schedule.symbols.as_ref().and_then(|m| m.get(symbol)) {
+      return 1.0 - fee / 100.0
+    }
+    let vip_fee = match (schedule.vip_level, &schedule.vip_taker_fees) {
+      (Some(level), Some(fees)) => fees.get(level).copied(),
+      _ => None,
+    };
+    let fee = match vip_fee {
+      Some(fee) => fee,
+      None => match self.fee {
+        Some(fee) => (1.0 - fee) * 100.0,
+        None => self.trading_taker_fee,
+      },
+    };
+    1.0 - fee * (1.0 - schedule.bnb_discount.unwrap_or(0.0) / 100.0) / 100.0
+  }
+
+  // 检查取值是否合理, 不访问交易所; 费率均为百分比, fee 为乘数
+  pub fn validate(&self) -> Validation {
+    let mut v = Validation::default();
+
+    if self.investment_base.trim().is_empty() {
+      v.error(ConfigIssue::Missing { field: "investment_base".to_string() });
+    }
+    v.positive("investment_min", self.investment_min);
+    v.positive("investment_max", self.investment_max);
+    v.positive("investment_step", self.investment_step);
+    if self.investment_min > self.investment_max {
+      v.error(ConfigIssue::MinAboveMax { min: self.investment_min, max: self.investment_max });
+    }
+    if self.investment_step > 0.0 && self.investment_step > self.investment_max - self.investment_min && self.investment_max > self.investment_min {
+      v.warn("investment_step", "larger than investment_max - investment_min, only investment_min is tried");
+    }
+
+    v.range("trading_taker_fee", self.trading_taker_fee, 0.0, 100.0);
+    if self.trading_taker_fee > 1.0 {
+      v.warn("trading_taker_fee", "is a percentage, values above 1 mean more than 1% per trade");
+    }
+    if let Some(fee) = self.fee {
+      if fee.is_nan() || fee <= 0.0 || fee > 1.0 {
+        v.error(ConfigIssue::OutOfRange { field: "fee".to_string(), value: fee, min: 0.0, max: 1.0 });
+      }
+    }
+    if let Some(schedule) = &self.fee_schedule {
+      if self.fee.is_some() {
+        v.warn("fee", "ignored for symbols covered by fee_schedule");
+      }
+      for (i, fee) in schedule.vip_taker_fees.iter().flatten().enumerate() {
+        v.range(&format!("fee_schedule.vip_taker_fees[{}]", i), *fee, 0.0, 100.0);
+      }
+      if let Some(level) = schedule.vip_level {
+        let levels = schedule.vip_taker_fees.as_ref().map_or(0, |fees| fees.len());
+        if level >= levels {
+          v.invalid("fee_schedule.vip_level", format!("{} has no entry in vip_taker_fees ({} levels)", level, levels));
+        }
+      }
+      if let Some(discount) = schedule.bnb_discount {
+        v.range("fee_schedule.bnb_discount", discount, 0.0, 100.0);
+      }
+      let mut symbols: Vec<(&String, &f64)> = schedule.symbols.iter().flatten().collect();
+      symbols.sort_by_key(|(symbol, _)| *symbol);
+      for (symbol, fee) in symbols {
+        v.range(&format!("fee_schedule.symbols.{}", symbol), *fee, 0.0, 100.0);
+      }
+    }
+
+    // 与 /threshold 一致, 阈值必须为正
+    if self.trading_profit_threshold < 0.0 {
+      v.invalid("trading_profit_threshold", format!("{} is negative, losing trades would be executed", self.trading_profit_threshold));
+    } else if self.trading_profit_threshold == 0.0 || self.trading_profit_threshold.is_nan() {
+      v.invalid("trading_profit_threshold", format!("is {}, must be positive", self.trading_profit_threshold));
+    }
+    if self.trading_age_threshold == 0 {
+      v.warn("trading_age_threshold", "is 0, stale tickers are not skipped");
+    } else if self.trading_age_threshold <= STREAM_INTERVAL_MS {
+      v.warn("trading_age_threshold", &format!("is not above the {}ms stream interval, most triangles are skipped as stale", STREAM_INTERVAL_MS));
+    }
+    if self.trading_execution_cap < 1 {
+      v.warn("trading_execution_cap", "is less than 1, treated as 1");
     }
+    if self.trading_enabled && !self.paper_trading.unwrap_or(true) {
+      if self.api_key.trim().is_empty() {
+        v.error(ConfigIssue::Missing { field: "api_key".to_string() });
+      }
+      if self.api_secret.trim().is_empty() {
+        v.error(ConfigIssue::Missing { field: "api_secret".to_string() });
+      }
+      v.warn("paper_trading", "is false, real orders are placed");
+    }
+
+    if self.depth_size < 0 {
+      v.invalid("depth_size", format!("{} is negative", self.depth_size));
+    } else if self.depth_size > 0 && ![5, 10, 20].contains(&self.depth_size) {
+      v.warn("depth_size", "binance only supports 5, 10 or 20 levels, rounded up");
+    } else if self.depth_size == 0 && self.depth_enabled.unwrap_or(false) {
+      v.invalid("depth_size", "is 0 while depth_enabled is true".to_string());
+    }
+    if let Some(quotes) = &self.base_quotes {
+      if quotes.is_empty() {
+        v.error(ConfigIssue::Missing { field: "base_quotes".to_string() });
+      }
+    }
+    if let (Some(allow), Some(exclude)) = (&self.allow_coins, &self.exclude_coins) {
+      if allow.iter().any(|c| exclude.contains(c)) {
+        v.warn("allow_coins", "overlaps exclude_coins, excluded coins are skipped");
+      }
+    }
+    if let Some(legs) = self.cycle_max_legs {
+      if legs < 3 {
+        v.invalid("cycle_max_legs", format!("is {}, cycles need at least 3 legs, cycle search would be off", legs));
+      } else {
+        v.range("cycle_max_legs", legs as f64, 3.0, 5.0);
+      }
+    }
+
+    let telegram_token = !self.telegram_token.trim().is_empty();
+    if telegram_token != (self.telegram_user_id != 0) {
+      v.warn("telegram", "telegram_token and telegram_user_id must both be set, notifications are disabled");
+    }
+    if let Some(ratio) = self.telegram_notify_ratio {
+      if ratio < 0.0 {
+        v.invalid("telegram_notify_ratio", format!("{} is negative", ratio));
+      }
+    }
+    if self.telegram_rate_per_minute == Some(0) {
+      v.invalid("telegram_rate_per_minute", "is 0, every message would be dropped".to_string());
+    }
+
+    if let Some(format) = &self.export_format {
+      if let Err(e) = ExportFormat::parse(format) {
+        v.invalid("export_format", reason(e));
+      }
+    }
+    if let Some(mb) = self.export_max_mb {
+      v.positive("export_max_mb", mb);
+    }
+    if self.export_dir.is_none() && (self.export_format.is_some() || self.export_max_mb.is_some() || self.export_hourly.is_some()) {
+      v.warn("export_dir", "not set, export options are ignored");
+    }
+    if let Some(format) = &self.log_format {
+      if let Err(e) = LogFormat::parse(format) {
+        v.invalid("log_format", reason(e));
+      }
+    }
+    if let Some(level) = &self.log_level {
+      if let Err(e) = parse_level(level) {
+        v.invalid("log_level", reason(e));
+      }
+    }
+    v
+  }
+
+  pub fn report_currency(&self) -> String {
+    self.report_currency.clone().unwrap_or_else(|| self.investment_base.clone())
+  }
+}
+
+// 命令行参数 <config> [--config <file>]... [其他参数]: 取出配置文件和其余参数
+// 第一个位置参数为基础配置, 每个 --config 叠加一个文件, 按出现顺序
+pub fn split_config_args(args: &[String]) -> Result<(Vec<String>, Vec<String>)> {
+  let mut paths: Vec<String> = Vec::new();
+  let mut overlays: Vec<String> = Vec::new();
+  let mut rest: Vec<String> = Vec::new();
+  let mut iter = args.iter();
+  while let Some(arg) = iter.next() {
+    if arg == "--config" {
+      let path = iter.next().ok_or_else(|| Error::Config("--config requires a file".to_string()))?;
+      overlays.push(path.clone());
+    } else if paths.is_empty() {
+      paths.push(arg.clone());
+    } else {
+      rest.push(arg.clone());
+    }
+  }
+  if paths.is_empty() {
+    return Err(Error::Config("no configuration file".to_string()))
+  }
+  paths.extend(overlays);
+  Ok((paths, rest))
+}
+
+// 读取一个配置文件, 扩展名为 toml 或 yaml/yml 时按对应格式解析, 其他按 json 解析
+fn read_config_file(path: &str) -> Result<Value> {
+  let err = |e: &dyn fmt::Display| Error::Config(format!("{}: {}", path, e));
+  let text = fs::read_to_string(path).map_err(|e| err(&e))?;
+  let ext = Path::new(path).extension().and_then(|x| x.to_str()).unwrap_or("").to_lowercase();
+  let value: Value = match ext.as_str() {
+    "toml" => toml::from_str(&text).map_err(|e| err(&e))?,
+    "yaml" | "yml" => serde_yaml_ng::from_str(&text).map_err(|e| err(&e))?,
+    _ => serde_json::from_str(&text).map_err(|e| err(&e))?,
+  };
+  if !value.is_object() {
+    return Err(err(&"top level must be a table/object"))
+  }
+  Ok(value)
+}
+
+// 把 layer 合并到 base: 两边都是对象时逐字段递归合并, 否则 layer 覆盖 base
+fn merge(base: &mut Value, layer: Value) {
+  match (base, layer) {
+    (Value::Object(base), Value::Object(layer)) => {
+      for (k, v) in layer {
+        match base.get_mut(&k) {
+          Some(b) => merge(b, v),
+          None => {
+            base.insert(k, v);
+          }
+        }
+      }
+    }
+    (base, layer) => *base = layer,
+  }
+}
+
+// 应用 TRIANGLE_* 环境变量, 只覆盖已知的顶层字段
+fn apply_env<I: IntoIterator<Item = (String, String)>>(value: &mut Value, env: I) -> Result<()> {
+  let vars: Vec<(String, String)> = env.into_iter()
+    .filter_map(|(k, v)| k.strip_prefix(ENV_PREFIX).map(|k| (k.to_lowercase(), v)))
+    .collect();
+  if vars.is_empty() {
+    return Ok(())
   }
-  // Parse config
-  fn parse_config_file<P: AsRef<Path>>(path: P) -> Result<Configuration, Box<dyn Error>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let u: Configuration = serde_json::from_reader(reader)?;
-    Ok(u)
+  // 文件已经是完整的配置时, 用它确定每个字段的类型 (包括未设置的可选字段)
+  let typed: Option<Value> = serde_json::from_value::<Configuration>(value.clone()).ok()
+    .and_then(|c| serde_json::to_value(c).ok());
+  let fields = typed.as_ref().unwrap_or(value).clone();
+  for (key, raw) in vars {
+    let current = match fields.get(&key) {
+      Some(v) => v,
+      None if typed.is_some() => continue,
+      None => &Value::Null,
+    };
+    let v = if current.is_string() {
+      Value::String(raw)
+    } else {
+      match serde_json::from_str(&raw) {
+        Ok(v) => v,
+        Err(_) if current.is_null() => Value::String(raw),
+        Err(e) => return Err(Error::Config(format!("{}{}: {}", ENV_PREFIX, key.to_uppercase(), e))),
+      }
+    };
+    value[key.as_str()] = v;
   }
+  Ok(())
 }
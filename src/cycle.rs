@@ -0,0 +1,268 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+
+use string_join::Join;
+
+use crate::trading_pair::TradingPair;
+use crate::tri_pair::{Side, Ticker};
+
+// 货币图的一条边: 通过交易对 pair 以 side 方向把 from 换成 to
+// 买入: quote -> base, 比例 1 / ask; 卖出: base -> quote, 比例 bid
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub pair: u32,
+    pub side: Side,
+    pub fee: f64,
+}
+
+// 套利环, assets[i] 经 legs[i] 换成 assets[i + 1], 最后换回 assets[0]
+#[derive(Debug, Clone)]
+pub struct Cycle {
+    pub name: String,
+    pub assets: Vec<String>,
+    pub legs: Vec<(u32, Side)>,
+    pub fees: Vec<f64>,
+}
+
+// 环上一轮的收益比例, 按最优价格和手续费计算
+#[derive(Debug, Default, Clone)]
+pub struct CycleProfit {
+    pub name: String,
+    pub legs: usize,
+    pub ratio: f64,
+}
+
+// 1 个 from 经该腿换得多少 to, 没有报价时为 None
+fn leg_rate(pair: u32, side: &Side, fee: f64, tickers: &[Ticker]) -> Option<f64> {
+    let tick = tickers.get(pair as usize)?;
+    let rate = if *side == Side::SideBuy {
+        if tick.ba[0] <= 0.0 {
+            return None
+        }
+        1.0 / tick.ba[0]
+    } else {
+        if tick.bb[0] <= 0.0 {
+            return None
+        }
+        tick.bb[0]
+    };
+    Some(rate * fee)
+}
+
+impl Cycle {
+    // 环经过的交易对
+    pub fn pairs(&self) -> Vec<u32> {
+        self.legs.iter().map(|(pair, _)| *pair).collect()
+    }
+
+    // 按最新 ticker (以交易对 id 为下标) 计算一轮的收益比例, 任一交易对没有报价时为 None
+    pub fn ratio(&self, tickers: &[Ticker]) -> Option<f64> {
+        let mut value = 1.0;
+        for ((pair, side), fee) in self.legs.iter().zip(self.fees.iter()) {
+            value *= leg_rate(*pair, side, *fee, tickers)?;
+        }
+        Some(value - 1.0)
+    }
+
+    // 去掉旋转后的唯一标识: 从交易对 id 最小的一腿开始
+    fn key(&self) -> Vec<(u32, bool)> {
+        let start = (0..self.legs.len()).min_by_key(|i| self.legs[*i].0).unwrap_or(0);
+        let n = self.legs.len();
+        (0..n).map(|i| {
+            let (pair, side) = &self.legs[(start + i) % n];
+            (*pair, *side == Side::SideBuy)
+        }).collect()
+    }
+}
+
+// 所有交易对组成的货币图, 边权为 -ln(换算比例 * 手续费), 权重之和为负的环即为套利环
+#[derive(Debug, Clone)]
+pub struct CurrencyGraph {
+    assets: Vec<String>,
+    edges: Vec<Edge>,
+}
+
+impl CurrencyGraph {
+    pub fn new(pairs: &[TradingPair]) -> CurrencyGraph {
+        let mut asset_idx: HashMap<String, usize> = HashMap::new();
+        let mut assets: Vec<String> = Vec::new();
+        let mut edges: Vec<Edge> = Vec::with_capacity(pairs.len() * 2);
+        let mut index = |asset: String| {
+            let n = asset_idx.len();
+            *asset_idx.entry(asset.clone()).or_insert_with(|| {
+                assets.push(asset);
+                n
+            })
+        };
+
+        for pair in pairs {
+            let base = index(pair.base());
+            let quote = index(pair.quote());
+            let idx = pair.get_symbol_idx();
+            edges.push(Edge { from: quote, to: base, pair: idx, side: Side::SideBuy, fee: pair.fee });
+            edges.push(Edge { from: base, to: quote, pair: idx, side: Side::SideSell, fee: pair.fee });
+        }
+
+        CurrencyGraph {
+            assets,
+            edges,
+        }
+    }
+
+    pub fn assets(&self) -> &[String] {
+        &self.assets
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    // 以最新 ticker (以交易对 id 为下标) 为边权, 对每个资产做限制边数的 Bellman-Ford, 找出 min_legs..=max_legs 条腿的负权简单环
+    // 每个资产只保留经过它的、各长度下收益最大的环, 结果按收益从大到小排序
+    pub fn find_cycles(&self, tickers: &[Ticker], min_legs: usize, max_legs: usize) -> Vec<Cycle> {
+        let n = self.assets.len();
+        let weights: Vec<Option<f64>> = self.edges.iter()
+            .map(|e| leg_rate(e.pair, &e.side, e.fee, tickers).filter(|r| *r > 0.0).map(|r| -r.ln()))
+            .collect();
+        let mut seen: HashSet<Vec<(u32, bool)>> = HashSet::new();
+        let mut cycles: Vec<(f64, Cycle)> = Vec::new();
+
+        for s in 0..n {
+            // dist[k][v]: 从 s 出发恰好 k 条边到达 v 的最小权重, pred[k][v] 为最后一条边
+            let mut dist = vec![vec![f64::INFINITY; n]; max_legs + 1];
+            let mut pred = vec![vec![usize::MAX; n]; max_legs + 1];
+            dist[0][s] = 0.0;
+            for k in 1..=max_legs {
+                for (ei, e) in self.edges.iter().enumerate() {
+                    let w = match weights[ei] {
+                        Some(w) => w,
+                        None => continue,
+                    };
+                    let d = dist[k - 1][e.from] + w;
+                    if d < dist[k][e.to] {
+                        dist[k][e.to] = d;
+                        pred[k][e.to] = ei;
+                    }
+                }
+            }
+
+            for (k, d) in dist.iter().enumerate().skip(min_legs) {
+                if d[s] >= -1e-12 {
+                    continue
+                }
+                let mut path: Vec<usize> = Vec::with_capacity(k);
+                let mut v = s;
+                for j in (1..=k).rev() {
+                    let ei = pred[j][v];
+                    path.push(ei);
+                    v = self.edges[ei].from;
+                }
+                path.reverse();
+                if let Some(cycle) = self.to_cycle(&path) {
+                    if seen.insert(cycle.key()) {
+                        cycles.push((d[s], cycle));
+                    }
+                }
+            }
+        }
+
+        cycles.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        cycles.into_iter().map(|(_, c)| c).collect()
+    }
+
+    // 由边组成环, 经过重复资产的路径不是简单环, 返回 None
+    fn to_cycle(&self, path: &[usize]) -> Option<Cycle> {
+        let mut visited: HashSet<usize> = HashSet::new();
+        for ei in path {
+            if !visited.insert(self.edges[*ei].from) {
+                return None
+            }
+        }
+        let assets: Vec<String> = path.iter().map(|ei| self.assets[self.edges[*ei].from].clone()).collect();
+        Some(Cycle {
+            name: "-".join(&assets),
+            assets,
+            legs: path.iter().map(|ei| (self.edges[*ei].pair, self.edges[*ei].side.clone())).collect(),
+            fees: path.iter().map(|ei| self.edges[*ei].fee).collect(),
+        })
+    }
+}
+
+// 已发现的环按交易对建立的索引, 以去掉旋转后的 key 去重
+// 每次搜索时刷新环的发现时间, 长时间未再被发现的环由 expire 删除, 索引大小受每次搜索的结果数量限制
+#[derive(Debug, Default, Clone)]
+pub struct CycleIndex {
+    seen: HashMap<Vec<(u32, bool)>, i64>, // 环 -> 最后一次被发现的时间, 毫秒
+    pairs: HashMap<u32, Vec<Cycle>>,
+}
+
+impl CycleIndex {
+    // 加入一次搜索的结果, ts 为搜索时间; 已存在的环只刷新发现时间, 返回新加入的环数量
+    pub fn add(&mut self, cycles: &[Cycle], ts: i64) -> usize {
+        let mut added = 0;
+        for cycle in cycles {
+            match self.seen.entry(cycle.key()) {
+                Entry::Occupied(mut e) => {
+                    *e.get_mut() = ts.max(*e.get());
+                    continue
+                }
+                Entry::Vacant(e) => {
+                    e.insert(ts);
+                }
+            }
+            for pair in cycle.pairs() {
+                self.pairs.entry(pair).or_default().push(cycle.clone());
+            }
+            added += 1;
+        }
+        added
+    }
+
+    // 删除 before 之前最后一次被发现的环, 返回删除的环数量
+    pub fn expire(&mut self, before: i64) -> usize {
+        let n = self.seen.len();
+        self.seen.retain(|_, last| *last >= before);
+        if self.seen.len() == n {
+            return 0
+        }
+        let seen = &self.seen;
+        self.pairs.retain(|_, list| {
+            list.retain(|c| seen.contains_key(&c.key()));
+            !list.is_empty()
+        });
+        n - self.seen.len()
+    }
+
+    // 交易对关联的环
+    pub fn get(&self, idx: u32) -> Option<&Vec<Cycle>> {
+        self.pairs.get(&idx)
+    }
+
+    // 索引中的环数量
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+}
+
+// 在后台线程中搜索套利环, 不阻塞扫描循环: 向返回的 Sender 发送 ticker 快照, 从 Receiver 取回搜索结果
+// Sender 容量为 0, 线程正在搜索时 try_send 失败, 扫描循环跳过这次快照; Sender 释放后线程退出
+pub fn spawn_discovery(graph: CurrencyGraph, max_legs: usize) -> (SyncSender<Vec<Ticker>>, Receiver<Vec<Cycle>>) {
+    let (snapshot_tx, snapshot_rx) = mpsc::sync_channel::<Vec<Ticker>>(0);
+    let (found_tx, found_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for tickers in snapshot_rx {
+            if found_tx.send(graph.find_cycles(&tickers, 3, max_legs)).is_err() {
+                break
+            }
+        }
+    });
+    (snapshot_tx, found_rx)
+}
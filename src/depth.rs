@@ -0,0 +1,486 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use binance::model::OrderBook;
+use chrono::prelude::Utc;
+use binance::websockets::WebSockets;
+use log::warn;
+
+use crate::config::STREAM_INTERVAL_MS;
+use crate::error::{Error, Result};
+use crate::market_data::{Backoff, MarketDataSource};
+use crate::metrics::Metrics;
+use crate::notifier::Notifier;
+use crate::trading_pair::PairFilters;
+use crate::tri_pair::{Side, Ticker, TriPair};
+
+// 交易对的部分深度, 每档为 [价格, 数量], asks 价格升序, bids 价格降序
+#[derive(Debug, Default, Clone)]
+pub struct Depth {
+    pub idx: u32,
+    pub asks: Vec<[f64; 2]>,
+    pub bids: Vec<[f64; 2]>,
+}
+
+impl Depth {
+    pub fn from(idx: u32, book: &OrderBook) -> Self {
+        Depth {
+            idx,
+            asks: book.asks.iter().map(|x| [x.price, x.qty]).collect(),
+            bids: book.bids.iter().map(|x| [x.price, x.qty]).collect(),
+        }
+    }
+
+    // 只有一档的深度
+    pub fn from_ticker(tick: &Ticker) -> Self {
+        Depth {
+            idx: tick.idx,
+            asks: vec![tick.ba],
+            bids: vec![tick.bb],
+        }
+    }
+
+    // 最优一档
+    pub fn ticker(&self) -> Ticker {
+        Ticker {
+            idx: self.idx,
+            ba: self.asks.first().copied().unwrap_or_default(),
+            bb: self.bids.first().copied().unwrap_or_default(),
+            ..Ticker::default()
+        }
+    }
+}
+
+// 所有交易对的最新深度, 数据源写入, 扫描循环读取
+pub type DepthBook = Arc<RwLock<HashMap<u32, Depth>>>;
+
+// binance 部分深度 (<symbol>@depth<levels>@100ms) 数据源
+// 最新深度写入 book, 同时把最优一档作为 ticker 发送, 扫描循环不需要区分数据源
+#[derive(Debug, Clone)]
+pub struct BinancePartialDepth {
+    levels: u32,
+    book: DepthBook,
+    bad: Arc<AtomicUsize>, // 无法解析而跳过的消息数量
+    notifier: Option<Notifier>, // ws 断开时通知
+    metrics: Option<Metrics>, // 记录重连次数
+}
+
+// 一个连接订阅的 stream 数量
+const STREAMS_PER_CONN: usize = 200;
+
+impl BinancePartialDepth {
+    // binance 只支持 5, 10, 20 档
+    pub fn new(depth_size: i32) -> BinancePartialDepth {
+        let levels = match depth_size {
+            n if n <= 5 => 5,
+            n if n <= 10 => 10,
+            _ => 20,
+        };
+        BinancePartialDepth {
+            levels,
+            book: Arc::new(RwLock::new(HashMap::new())),
+            bad: Arc::new(AtomicUsize::new(0)),
+            notifier: None,
+            metrics: None,
+        }
+    }
+
+    pub fn with_notifier(mut self, notifier: Option<Notifier>) -> BinancePartialDepth {
+        self.notifier = notifier;
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: Option<Metrics>) -> BinancePartialDepth {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn book(&self) -> DepthBook {
+        self.book.clone()
+    }
+
+    // 无法解析而跳过的消息数量
+    pub fn bad_tickers(&self) -> usize {
+        self.bad.load(Ordering::Relaxed)
+    }
+}
+
+impl MarketDataSource for BinancePartialDepth {
+    fn subscribe(&mut self, symbol_id_map: HashMap<String, u32>) -> Receiver<Ticker> {
+        let (tx, rx): (Sender<Ticker>, Receiver<Ticker>) = mpsc::channel();
+        let symbols: Vec<(String, u32)> = symbol_id_map.into_iter().collect();
+
+        for chunk in symbols.chunks(STREAMS_PER_CONN) {
+            let ids: HashMap<String, u32> = chunk.iter().cloned().collect();
+            let streams: Vec<String> = ids.keys().map(|s| format!("{}@depth{}@{}ms", s.to_lowercase(), self.levels, STREAM_INTERVAL_MS)).collect();
+            let book = self.book.clone();
+            let bad = self.bad.clone();
+            let notifier = self.notifier.clone();
+            let metrics = self.metrics.clone();
+            let tx = tx.clone();
+
+            // 扫描循环退出 (队列断开) 时 run_depth_streams 返回 Ok, 不再重连; 出错时按 Backoff 退避重连
+            thread::spawn(move || {
+                let mut backoff = Backoff::default();
+                loop {
+                    let mut received = false;
+                    let err = match run_depth_streams(&streams, &ids, &book, &bad, &tx, &mut received) {
+                        Ok(()) => break,
+                        Err(e) => e,
+                    };
+                    if received {
+                        backoff.reset();
+                    }
+                    let delay = backoff.next_delay();
+                    warn!(error:% = err, delay_ms = delay.as_millis() as u64; "depth ws error, reconnecting");
+                    if let Some(notifier) = &notifier {
+                        notifier.notify(&format!("depth ws error occurs: {}, reconnect in {:?}", err, delay));
+                    }
+                    if let Some(metrics) = &metrics {
+                        metrics.ws_reconnected();
+                    }
+                    thread::sleep(delay);
+                }
+            });
+        }
+
+        rx
+    }
+}
+
+// 组合 stream 的部分深度消息不带 symbol, 需要从 stream 名称中取得, 因此直接读取原始消息
+// 无法解析的消息跳过并计入 bad, 收到有效深度时 received 置为 true
+#[allow(clippy::result_large_err)]
+fn run_depth_streams(
+    streams: &[String],
+    ids: &HashMap<String, u32>,
+    book: &DepthBook,
+    bad: &AtomicUsize,
+    tx: &Sender<Ticker>,
+    received: &mut bool,
+) -> Result<()> {
+    let mut web_socket: WebSockets<'_> = WebSockets::new(|_| Ok(()));
+    web_socket.connect_multiple_streams(streams).map_err(|e| Error::WebSocket(e.to_string()))?;
+    let (socket, _) = web_socket.socket.as_mut().ok_or_else(|| Error::WebSocket("depth ws not connected".to_string()))?;
+
+    loop {
+        let msg = socket.read_message().map_err(|e| Error::WebSocket(e.to_string()))?;
+        if msg.is_close() {
+            return Err(Error::WebSocket("depth ws closed".to_string()))
+        }
+        if !msg.is_text() {
+            continue
+        }
+        let (idx, data) = match parse_depth_message(&msg.to_string(), ids) {
+            Ok(Some(v)) => v,
+            Ok(None) => continue,
+            Err(e) => {
+                let n = bad.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(error:% = e, skipped = n; "skip malformed depth message");
+                continue
+            }
+        };
+        *received = true;
+        let depth = Depth::from(idx, &data);
+        let tick = Ticker {
+            update_id: data.last_update_id,
+            ts: Utc::now().timestamp_millis(),
+            ..depth.ticker()
+        };
+        book.write().unwrap().insert(idx, depth);
+        if tx.send(tick).is_err() {
+            return Ok(())
+        }
+    }
+}
+
+// 解析组合 stream 的消息, 不是订阅的交易对时返回 None
+fn parse_depth_message(text: &str, ids: &HashMap<String, u32>) -> Result<Option<(u32, OrderBook)>> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| Error::Parse(e.to_string()))?;
+    let symbol = value.get("stream").and_then(|s| s.as_str()).and_then(|s| s.split('@').next())
+        .ok_or_else(|| Error::Parse("missing stream".to_string()))?
+        .to_uppercase();
+    let idx = match ids.get(&symbol) {
+        Some(idx) => *idx,
+        None => return Ok(None),
+    };
+    let data = value.get("data").ok_or_else(|| Error::Parse(format!("{} missing data", symbol)))?;
+    let data: OrderBook = serde_json::from_value(data.clone()).map_err(|e| Error::Parse(format!("{}: {}", symbol, e)))?;
+    Ok(Some((idx, data)))
+}
+
+// 按数量吃单, 返回 (成交数量, 成交额), 深度不足时只成交可成交的部分
+fn take_qty(levels: &[[f64; 2]], qty: f64) -> (f64, f64) {
+    let mut left = qty;
+    let mut notional = 0.0;
+    for [price, size] in levels {
+        if left <= 0.0 {
+            break
+        }
+        let q = left.min(*size);
+        notional += q * price;
+        left -= q;
+    }
+    (qty - left, notional)
+}
+
+// 按成交额吃单, 返回 (成交数量, 成交额)
+fn take_notional(levels: &[[f64; 2]], notional: f64) -> (f64, f64) {
+    let mut left = notional;
+    let mut qty = 0.0;
+    for [price, size] in levels {
+        if left <= 0.0 {
+            break
+        }
+        let n = left.min(price * size);
+        qty += n / price;
+        left -= n;
+    }
+    (qty, notional - left)
+}
+
+// 深度不足, 成交的 done 少于 want
+fn short(done: f64, want: f64) -> bool {
+    done < want * (1.0 - 1e-9)
+}
+
+fn cumsum<I: Iterator<Item = f64>>(it: I) -> Vec<f64> {
+    let mut total = 0.0;
+    it.map(|x| { total += x; total }).collect()
+}
+
+// 边际收益曲线上的一点: 第一条腿成交 amount 个 base 时的收益, 以及上一点到该点之间每个 base 的边际收益
+#[derive(Debug, Default, Clone)]
+pub struct CurvePoint {
+    pub amount: f64,
+    pub profit: f64,
+    pub marginal: f64,
+}
+
+// 多档深度下一个方向的最优成交量
+#[derive(Debug, Default, Clone)]
+pub struct DepthProfit {
+    pub rev: bool,
+    pub amount: f64, // 第一条腿成交的 base 数量
+    pub cost: f64,   // 第一条腿投入的数量, 起始资产
+    pub profit: f64, // 起始资产计
+    pub curve: Vec<CurvePoint>,
+}
+
+// 多档深度下的一条腿: 买入吃 asks, 卖出吃 bids
+struct Leg<'a> {
+    book: &'a Depth,
+    side: Side,
+    fee: f64,
+    filters: &'a PairFilters,
+}
+
+impl<'a> Leg<'a> {
+    // 投入 x 的成交: (实际投入, 扣除手续费后得到)
+    // 第一条腿的投入按 base 数量计, 之后的腿投入上一条腿得到的资产: 买入为 quote 金额, 卖出为 base 数量
+    fn take(&self, first: bool, x: f64) -> (f64, f64) {
+        match (&self.side, first) {
+            (Side::SideBuy, true) => {
+                let (qty, _) = take_qty(&self.book.asks, x);
+                (qty, qty * self.fee)
+            }
+            (Side::SideBuy, false) => {
+                let (qty, spent) = take_notional(&self.book.asks, x);
+                (spent, qty * self.fee)
+            }
+            (Side::SideSell, _) => {
+                let (sold, got) = take_qty(&self.book.bids, x);
+                (sold, got * self.fee)
+            }
+        }
+    }
+
+    // 第二, 三条腿投入上一条腿得到的 x, 成交的 base 数量按 filters 取整和校验, 与 TriPair::fill_legs 一致
+    // 返回扣除手续费后得到的资产, 深度不足或不满足 filters 时返回 None
+    fn fill(&self, x: f64) -> Option<f64> {
+        let levels = if self.side == Side::SideBuy { &self.book.asks } else { &self.book.bids };
+        let want = if self.side == Side::SideBuy {
+            let (qty, spent) = take_notional(levels, x);
+            if short(spent, x) {
+                return None
+            }
+            qty
+        } else {
+            x
+        };
+        let qty = self.filters.round_qty(want);
+        let (done, notional) = take_qty(levels, qty);
+        if short(done, qty) || !self.filters.accepts(qty, notional) {
+            return None
+        }
+        Some(if self.side == Side::SideBuy { qty * self.fee } else { notional * self.fee })
+    }
+
+    // 得到 y 需要的投入, 深度不足时按全部深度计算
+    fn input_for(&self, first: bool, y: f64) -> f64 {
+        let y = y / self.fee;
+        match (&self.side, first) {
+            (Side::SideBuy, true) => y,
+            (Side::SideBuy, false) => take_qty(&self.book.asks, y).1,
+            (Side::SideSell, _) => take_notional(&self.book.bids, y).0,
+        }
+    }
+
+    // 每一档的边界, 以投入计
+    fn levels(&self, first: bool) -> Vec<f64> {
+        match (&self.side, first) {
+            (Side::SideBuy, true) => cumsum(self.book.asks.iter().map(|x| x[1])),
+            (Side::SideBuy, false) => cumsum(self.book.asks.iter().map(|x| x[0] * x[1])),
+            (Side::SideSell, _) => cumsum(self.book.bids.iter().map(|x| x[1])),
+        }
+    }
+}
+
+// 多档深度下按执行顺序的三条腿, 第一条腿成交 vol 个 base, 之后每条腿投入上一条腿得到的全部资产
+struct Legs<'a> {
+    legs: [Leg<'a>; 3],
+}
+
+impl<'a> Legs<'a> {
+    // 第一条腿成交 vol 个 base 的投入和收回, 以起始资产计, 深度不足时返回 None
+    fn eval(&self, vol: f64) -> Option<(f64, f64)> {
+        let cost = self.notional_cost(vol);
+        let mut holding = vol;
+        for (n, leg) in self.legs.iter().enumerate() {
+            let (done, got) = leg.take(n == 0, holding);
+            if short(done, holding) {
+                return None
+            }
+            holding = got;
+        }
+        Some((cost, holding))
+    }
+
+    // 与 eval 相同, 第二, 三条腿按交易对的 filters 成交, 中间资产的零头不计入收益; vol 已按第一条腿的 filters 取整
+    fn fill(&self, vol: f64) -> Option<(f64, f64)> {
+        let (done, mut holding) = self.legs[0].take(true, vol);
+        if short(done, vol) {
+            return None
+        }
+        for leg in &self.legs[1..] {
+            holding = leg.fill(holding)?;
+        }
+        Some((self.notional_cost(vol), holding))
+    }
+
+    // 第一条腿成交 vol 个 base 的投入, 以起始资产计
+    fn notional_cost(&self, vol: f64) -> f64 {
+        if self.legs[0].side == Side::SideBuy { self.notional(vol) } else { vol }
+    }
+
+    // 第一条腿成交 vol 个 base 的成交额
+    fn notional(&self, vol: f64) -> f64 {
+        let first = &self.legs[0];
+        let levels = if first.side == Side::SideBuy { &first.book.asks } else { &first.book.bids };
+        take_qty(levels, vol).1
+    }
+
+    // 第 n 条腿投入 x 时对应的第一条腿成交数量
+    fn to_vol(&self, n: usize, x: f64) -> f64 {
+        (0..n).rev().fold(x, |x, i| self.legs[i].input_for(i == 0, x))
+    }
+
+    // 收益是成交量的分段线性凹函数, 最大值在某一档的边界上; 把三条腿每一档的边界都换算为第一条腿的成交数量
+    fn breakpoints(&self) -> Vec<f64> {
+        let mut points: Vec<f64> = Vec::new();
+        for (n, leg) in self.legs.iter().enumerate() {
+            points.extend(leg.levels(n == 0).into_iter().map(|x| self.to_vol(n, x)));
+        }
+
+        let max = self.max_vol();
+        let mut points: Vec<f64> = points.into_iter().filter(|x| *x > 0.0 && *x <= max).collect();
+        points.push(max);
+        points.sort_by(f64::total_cmp);
+        points.dedup_by(|a, b| (*a - *b).abs() < 1e-12);
+        points
+    }
+
+    // 三条腿深度都足够的最大成交数量
+    fn max_vol(&self) -> f64 {
+        self.legs.iter().enumerate()
+            .map(|(n, leg)| self.to_vol(n, leg.levels(n == 0).last().copied().unwrap_or(0.0)))
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+fn depth_legs<'a>(tp: &'a TriPair, rev: bool, books: [&'a Depth; 3]) -> (usize, Legs<'a>) {
+    let leg = |(idx, side): &(u32, Side)| {
+        let k = tp.pairs.iter().position(|p| p == idx).unwrap_or(0);
+        Leg { book: books[k], side: side.clone(), fee: tp.fees[k], filters: &tp.filters[k] }
+    };
+    let [l0, l1, l2] = tp.legs(rev);
+    (rev as usize, Legs { legs: [leg(&l0), leg(&l1), leg(&l2)] })
+}
+
+// 沿多档深度成交第一条腿 amount 个 base, 返回 (成交数量, 投入, 收回), 起始资产计
+// 每条腿都按交易对的 filters 取整和校验, 任意一条腿无法成交时返回 None
+pub fn eval_depth(tp: &TriPair, rev: bool, books: [&Depth; 3], amount: f64) -> Option<(f64, f64, f64)> {
+    let (a, legs) = depth_legs(tp, rev, books);
+    let filters = &tp.filters[a];
+    let amount = filters.round_qty(amount);
+    if !filters.accepts(amount, legs.notional(amount)) {
+        return None
+    }
+    legs.fill(amount).map(|(cost, end)| (amount, cost, end))
+}
+
+// 沿三条腿的多档深度计算 rev 方向收益最大的成交量, books 与 tp.pairs 顺序一致
+// 最优成交量按第一条腿的 filters 取整, 三条腿都按交易对的 filters 成交, 无法成交或没有盈利时返回 None
+pub fn calc_depth_profit(tp: &TriPair, rev: bool, books: [&Depth; 3]) -> Option<DepthProfit> {
+    let (a, legs) = depth_legs(tp, rev, books);
+
+    let mut curve: Vec<CurvePoint> = Vec::new();
+    let mut last = CurvePoint::default();
+    for vol in legs.breakpoints() {
+        let (cost, end) = match legs.eval(vol) {
+            Some(v) => v,
+            None => break,
+        };
+        let point = CurvePoint {
+            amount: vol,
+            profit: end - cost,
+            marginal: (end - cost - last.profit) / (vol - last.amount),
+        };
+        last = point.clone();
+        curve.push(point);
+    }
+
+    let best = curve.iter().max_by(|x, y| x.profit.total_cmp(&y.profit))?;
+    let filters = &tp.filters[a];
+    let amount = filters.round_qty(best.amount);
+    if !filters.accepts(amount, legs.notional(amount)) {
+        return None
+    }
+    let (cost, end) = legs.fill(amount)?;
+    if end <= cost {
+        return None
+    }
+
+    Some(DepthProfit {
+        rev,
+        amount,
+        cost,
+        profit: end - cost,
+        curve,
+    })
+}
+
+// 两个方向中收益较大的一个, 反向的收益以反向的起始资产计, 按桥交易对最优价格换算后比较
+pub fn best_depth_profit(tp: &TriPair, books: [&Depth; 3]) -> Option<DepthProfit> {
+    let forward = calc_depth_profit(tp, false, books);
+    let reverse = calc_depth_profit(tp, true, books);
+    let bridge = books[2].ticker();
+    let to_q1 = |v: f64| if tp.dirs[2] == Side::SideSell { v * bridge.bb[0] } else { v / bridge.ba[0] };
+    match (forward, reverse) {
+        (Some(f), Some(r)) => if f.profit < to_q1(r.profit) { Some(r) } else { Some(f) },
+        (f, r) => f.or(r),
+    }
+}
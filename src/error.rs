@@ -0,0 +1,60 @@
+use std::fmt;
+use std::io;
+
+// crate 统一的错误类型, 配置、行情解析、交易所接口和 ws 连接的错误都转换为 Error 返回, 不再 panic
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Config(String),    // 配置文件格式错误
+    Parse(String),     // 行情或交易对数据无法解析
+    Exchange(String),  // 交易所 REST 接口错误
+    WebSocket(String), // ws 连接或读取错误
+    Storage(String),   // 机会存储 (SQLite、导出文件) 写入错误
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::Config(e) => write!(f, "config error: {}", e),
+            Error::Parse(e) => write!(f, "parse error: {}", e),
+            Error::Exchange(e) => write!(f, "exchange error: {}", e),
+            Error::WebSocket(e) => write!(f, "websocket error: {}", e),
+            Error::Storage(e) => write!(f, "storage error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<binance::errors::Error> for Error {
+    fn from(e: binance::errors::Error) -> Error {
+        Error::Exchange(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Error {
+        Error::Storage(e.to_string())
+    }
+}
+
+// 解析交易所返回的数字字段, field 用于错误信息
+pub fn parse_f64(field: &str, value: &str) -> Result<f64> {
+    value.parse().map_err(|_| Error::Parse(format!("invalid {}: {:?}", field, value)))
+}
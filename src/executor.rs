@@ -0,0 +1,40 @@
+use crate::config::Configuration;
+use crate::live::LiveExecutor;
+use crate::notifier::Notifier;
+use crate::paper::PaperExecutor;
+use crate::simulate::Execution;
+use crate::trading_pair::TradingPair;
+use crate::tri_pair::{Profit, TriPair};
+
+// 一次执行的结果, 扫描循环只在 Submitted 或 Executed 时把机会标记为已执行
+#[derive(Debug)]
+pub enum Outcome {
+    Skipped,             // 未执行: 余额不足、数量过小、超过并发上限等, 机会仍可再次执行
+    Submitted,           // 已提交, 异步执行, 结果由执行器报告
+    Executed(Execution), // 同步执行完成
+}
+
+// 交易执行
+// 扫描循环发现超过 trading_profit_threshold 的机会时调用, 按 tp.legs(profit.rev) 的顺序执行三条腿
+pub trait Executor {
+    // pairs 与 tp.pairs 顺序一致
+    fn execute(&mut self, tp: &TriPair, profit: &Profit, pairs: [&TradingPair; 3]) -> Outcome;
+
+    // 扫描循环退出时调用, 异步执行的执行器在这里等待进行中的交易结束
+    fn shutdown(&mut self) {}
+
+    // 异步执行的执行器通过通知器报告执行结果, 同步执行的结果由扫描循环通知
+    fn set_notifier(&mut self, _notifier: Notifier) {}
+}
+
+// 根据配置创建执行器, trading_enabled 为 false 时不交易
+// paper_trading 明确设置为 false 时实盘交易, 否则模拟交易
+pub fn from_config(config: &Configuration) -> Option<Box<dyn Executor>> {
+    if !config.trading_enabled {
+        return None
+    }
+    if config.paper_trading == Some(false) {
+        return Some(Box::new(LiveExecutor::new(config)))
+    }
+    Some(Box::new(PaperExecutor::new(config)))
+}
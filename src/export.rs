@@ -0,0 +1,417 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{TimeZone, Utc};
+use log::error;
+use serde::Serialize;
+
+pub use crate::config::ExportFormat;
+use crate::config::Configuration;
+use crate::error::{Error, Result};
+use crate::sink::OpportunitySink;
+use crate::tri_pair::{Profit, Ticker};
+
+// 按小时轮换时每个文件覆盖的时间, 毫秒
+const HOUR_MS: u64 = 3_600_000;
+
+// 导出文件的一行: 扫描循环中一次收益为正且超过 trading_profit_threshold 的计算结果及其三条腿的 ticker
+// 机会持续期间每个 ticker 一行, 机会的开始和结束见 SQLite 或日志; 字段顺序即文件的列顺序, 修改时只能在末尾追加
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportRow {
+    pub ts: u64,
+    pub name: String,
+    pub rev: bool,
+    pub ratio: f64,
+    pub amount: f64,
+    pub profit: f64,
+    pub asset: String,
+    pub value: f64,
+    pub invest: f64,
+    pub symbol1: String,
+    pub ask1: f64,
+    pub ask_qty1: f64,
+    pub bid1: f64,
+    pub bid_qty1: f64,
+    pub update_id1: u64,
+    pub ticker_ts1: i64,
+    pub symbol2: String,
+    pub ask2: f64,
+    pub ask_qty2: f64,
+    pub bid2: f64,
+    pub bid_qty2: f64,
+    pub update_id2: u64,
+    pub ticker_ts2: i64,
+    pub symbol3: String,
+    pub ask3: f64,
+    pub ask_qty3: f64,
+    pub bid3: f64,
+    pub bid_qty3: f64,
+    pub update_id3: u64,
+    pub ticker_ts3: i64,
+}
+
+impl ExportRow {
+    pub fn new(profit: &Profit, symbols: [&str; 3]) -> ExportRow {
+        let [t1, t2, t3]: &[Ticker; 3] = &profit.tickers;
+        ExportRow {
+            ts: profit.ts,
+            name: profit.name.clone(),
+            rev: profit.rev,
+            ratio: profit.ratio,
+            amount: profit.amount,
+            profit: profit.profit,
+            asset: profit.asset.clone(),
+            value: profit.value,
+            invest: profit.invest,
+            symbol1: symbols[0].to_string(),
+            ask1: t1.ba[0],
+            ask_qty1: t1.ba[1],
+            bid1: t1.bb[0],
+            bid_qty1: t1.bb[1],
+            update_id1: t1.update_id,
+            ticker_ts1: t1.ts,
+            symbol2: symbols[1].to_string(),
+            ask2: t2.ba[0],
+            ask_qty2: t2.ba[1],
+            bid2: t2.bb[0],
+            bid_qty2: t2.bb[1],
+            update_id2: t2.update_id,
+            ticker_ts2: t2.ts,
+            symbol3: symbols[2].to_string(),
+            ask3: t3.ba[0],
+            ask_qty3: t3.ba[1],
+            bid3: t3.bb[0],
+            bid_qty3: t3.bb[1],
+            update_id3: t3.update_id,
+            ticker_ts3: t3.ts,
+        }
+    }
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+// 当前写入的文件
+trait RowWriter: Send {
+    fn write(&mut self, row: &ExportRow) -> Result<()>;
+    // 已写入的字节数, 用于按大小轮换
+    fn bytes(&self) -> u64;
+    fn flush(&mut self) -> Result<()>;
+    // 写完文件 (Parquet 写入文件尾)
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+// 统计写入的字节数
+struct Counting<W: Write> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct CsvWriter {
+    writer: csv::Writer<Counting<File>>, // csv 自带缓冲
+}
+
+impl CsvWriter {
+    fn create(path: &Path) -> Result<CsvWriter> {
+        let file = Counting { inner: File::create(path)?, bytes: 0 };
+        Ok(CsvWriter { writer: csv::Writer::from_writer(file) })
+    }
+}
+
+impl RowWriter for CsvWriter {
+    fn write(&mut self, row: &ExportRow) -> Result<()> {
+        self.writer.serialize(row).map_err(|e| Error::Storage(e.to_string()))
+    }
+
+    fn bytes(&self) -> u64 {
+        self.writer.get_ref().bytes
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_writer {
+    use std::fs::File;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray, UInt64Array};
+    use arrow_schema::{DataType, Field, Schema, SchemaRef};
+    use parquet::arrow::ArrowWriter;
+
+    use super::{ExportRow, RowWriter};
+    use crate::error::{Error, Result};
+
+    // 缓存的行数达到该值时写入一个 RecordBatch
+    const BATCH_ROWS: usize = 1024;
+
+    fn schema() -> SchemaRef {
+        let mut fields = vec![
+            Field::new("ts", DataType::UInt64, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("rev", DataType::Boolean, false),
+            Field::new("ratio", DataType::Float64, false),
+            Field::new("amount", DataType::Float64, false),
+            Field::new("profit", DataType::Float64, false),
+            Field::new("asset", DataType::Utf8, false),
+            Field::new("value", DataType::Float64, false),
+            Field::new("invest", DataType::Float64, false),
+        ];
+        for i in 1..=3 {
+            fields.push(Field::new(format!("symbol{}", i), DataType::Utf8, false));
+            fields.push(Field::new(format!("ask{}", i), DataType::Float64, false));
+            fields.push(Field::new(format!("ask_qty{}", i), DataType::Float64, false));
+            fields.push(Field::new(format!("bid{}", i), DataType::Float64, false));
+            fields.push(Field::new(format!("bid_qty{}", i), DataType::Float64, false));
+            fields.push(Field::new(format!("update_id{}", i), DataType::UInt64, false));
+            fields.push(Field::new(format!("ticker_ts{}", i), DataType::Int64, false));
+        }
+        Arc::new(Schema::new(fields))
+    }
+
+    fn err<E: std::fmt::Display>(e: E) -> Error {
+        Error::Storage(e.to_string())
+    }
+
+    pub(super) struct ParquetWriter {
+        writer: ArrowWriter<File>,
+        schema: SchemaRef,
+        rows: Vec<ExportRow>,
+    }
+
+    impl ParquetWriter {
+        pub(super) fn create(path: &Path) -> Result<ParquetWriter> {
+            let schema = schema();
+            let writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), None).map_err(err)?;
+            Ok(ParquetWriter { writer, schema, rows: vec![] })
+        }
+
+        fn write_batch(&mut self) -> Result<()> {
+            if self.rows.is_empty() {
+                return Ok(())
+            }
+            let rows = std::mem::take(&mut self.rows);
+            let f64s = |f: fn(&ExportRow) -> f64| Arc::new(Float64Array::from_iter_values(rows.iter().map(f))) as ArrayRef;
+            let strs = |f: fn(&ExportRow) -> &str| Arc::new(StringArray::from_iter_values(rows.iter().map(f))) as ArrayRef;
+            let u64s = |f: fn(&ExportRow) -> u64| Arc::new(UInt64Array::from_iter_values(rows.iter().map(f))) as ArrayRef;
+            let i64s = |f: fn(&ExportRow) -> i64| Arc::new(Int64Array::from_iter_values(rows.iter().map(f))) as ArrayRef;
+            let columns = vec![
+                u64s(|r| r.ts),
+                strs(|r| &r.name),
+                Arc::new(rows.iter().map(|r| Some(r.rev)).collect::<BooleanArray>()) as ArrayRef,
+                f64s(|r| r.ratio),
+                f64s(|r| r.amount),
+                f64s(|r| r.profit),
+                strs(|r| &r.asset),
+                f64s(|r| r.value),
+                f64s(|r| r.invest),
+                strs(|r| &r.symbol1),
+                f64s(|r| r.ask1),
+                f64s(|r| r.ask_qty1),
+                f64s(|r| r.bid1),
+                f64s(|r| r.bid_qty1),
+                u64s(|r| r.update_id1),
+                i64s(|r| r.ticker_ts1),
+                strs(|r| &r.symbol2),
+                f64s(|r| r.ask2),
+                f64s(|r| r.ask_qty2),
+                f64s(|r| r.bid2),
+                f64s(|r| r.bid_qty2),
+                u64s(|r| r.update_id2),
+                i64s(|r| r.ticker_ts2),
+                strs(|r| &r.symbol3),
+                f64s(|r| r.ask3),
+                f64s(|r| r.ask_qty3),
+                f64s(|r| r.bid3),
+                f64s(|r| r.bid_qty3),
+                u64s(|r| r.update_id3),
+                i64s(|r| r.ticker_ts3),
+            ];
+            let batch = RecordBatch::try_new(self.schema.clone(), columns).map_err(err)?;
+            self.writer.write(&batch).map_err(err)
+        }
+    }
+
+    impl RowWriter for ParquetWriter {
+        fn write(&mut self, row: &ExportRow) -> Result<()> {
+            self.rows.push(row.clone());
+            if self.rows.len() >= BATCH_ROWS {
+                self.write_batch()?;
+            }
+            Ok(())
+        }
+
+        fn bytes(&self) -> u64 {
+            (self.writer.bytes_written() + self.writer.in_progress_size()) as u64
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            self.write_batch()?;
+            self.writer.flush().map_err(err)
+        }
+
+        fn finish(mut self: Box<Self>) -> Result<()> {
+            self.write_batch()?;
+            self.writer.close().map_err(err)?;
+            Ok(())
+        }
+    }
+}
+
+// 把超过阈值的收益导出到 dir 下轮换的 CSV 或 Parquet 文件, 每个文件的列相同 (ExportRow)
+// 文件名为 opportunities-<第一行的 UTC 时间>.<csv|parquet>, 同名文件已存在时追加序号
+// 超过 max_bytes 或进入新的小时 (hourly, 按机会的时间) 时换到新文件
+pub struct ExportSink {
+    dir: PathBuf,
+    format: ExportFormat,
+    max_bytes: Option<u64>,
+    hourly: bool,
+    current: Option<(Box<dyn RowWriter>, u64)>, // 当前文件及其第一行所在的小时
+    files: Vec<PathBuf>, // 已创建的文件
+}
+
+impl std::fmt::Debug for ExportSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ExportSink")
+            .field("dir", &self.dir)
+            .field("format", &self.format)
+            .field("max_bytes", &self.max_bytes)
+            .field("hourly", &self.hourly)
+            .field("files", &self.files)
+            .finish()
+    }
+}
+
+impl ExportSink {
+    // dir 不存在时创建
+    pub fn new(dir: &str, format: ExportFormat, max_bytes: Option<u64>, hourly: bool) -> Result<ExportSink> {
+        fs::create_dir_all(dir)?;
+        Ok(ExportSink {
+            dir: PathBuf::from(dir),
+            format,
+            max_bytes,
+            hourly,
+            current: None,
+            files: vec![],
+        })
+    }
+
+    // export_dir 未设置时返回 None
+    pub fn from_config(config: &Configuration) -> Result<Option<ExportSink>> {
+        let dir = match &config.export_dir {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+        let format = ExportFormat::parse(config.export_format.as_deref().unwrap_or("csv"))?;
+        let max_bytes = config.export_max_mb.map(|mb| (mb * 1024.0 * 1024.0) as u64);
+        let sink = ExportSink::new(dir, format, max_bytes, config.export_hourly.unwrap_or(false))?;
+        Ok(Some(sink))
+    }
+
+    // 已创建的文件, 按创建顺序
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    fn new_path(&self, ts: u64) -> PathBuf {
+        let stamp = Utc.timestamp_millis_opt(ts as i64).single()
+            .unwrap_or_else(Utc::now)
+            .format("%Y%m%d-%H%M%S");
+        let ext = self.format.extension();
+        let mut path = self.dir.join(format!("opportunities-{}.{}", stamp, ext));
+        let mut seq = 1;
+        while path.exists() {
+            path = self.dir.join(format!("opportunities-{}-{}.{}", stamp, seq, ext));
+            seq += 1;
+        }
+        path
+    }
+
+    fn create(&self, path: &Path) -> Result<Box<dyn RowWriter>> {
+        match self.format {
+            ExportFormat::Csv => Ok(Box::new(CsvWriter::create(path)?)),
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => Ok(Box::new(parquet_writer::ParquetWriter::create(path)?)),
+            #[cfg(not(feature = "parquet"))]
+            ExportFormat::Parquet => Err(Error::Config("export_format parquet requires the parquet feature".to_string())),
+        }
+    }
+
+    fn finish_current(&mut self) -> Result<()> {
+        match self.current.take() {
+            Some((writer, _)) => writer.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl OpportunitySink for ExportSink {
+    // 机会开始时的收益同样经过 record_profit, 不重复写入
+    fn record(&mut self, _profit: &Profit, _symbols: [&str; 3]) -> Result<()> {
+        Ok(())
+    }
+
+    fn record_profit(&mut self, profit: &Profit, symbols: [&str; 3]) -> Result<()> {
+        let hour = profit.ts / HOUR_MS;
+        let rotate = match &self.current {
+            Some((writer, h)) => (self.hourly && *h != hour) || self.max_bytes.is_some_and(|max| writer.bytes() >= max),
+            None => false,
+        };
+        if rotate {
+            self.finish_current()?;
+        }
+        if self.current.is_none() {
+            let path = self.new_path(profit.ts);
+            let writer = self.create(&path)?;
+            self.files.push(path);
+            self.current = Some((writer, hour));
+        }
+        let (writer, _) = self.current.as_mut().unwrap();
+        writer.write(&ExportRow::new(profit, symbols))
+    }
+
+    // CSV 刷新缓冲; Parquet 写入文件尾, 之后的机会写入新文件
+    fn flush(&mut self) -> Result<()> {
+        match self.format {
+            ExportFormat::Csv => match &mut self.current {
+                Some((writer, _)) => writer.flush(),
+                None => Ok(()),
+            },
+            ExportFormat::Parquet => self.finish_current(),
+        }
+    }
+}
+
+impl Drop for ExportSink {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish_current() {
+            error!(error:% = e; "close export file failed");
+        }
+    }
+}
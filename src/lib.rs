@@ -1,5 +1,25 @@
+pub mod error;
+pub mod logger;
 pub mod config;
 pub mod trading_pair;
 pub mod tri_pair;
+pub mod cycle;
+pub mod market_data;
+pub mod shutdown;
+pub mod depth;
 pub mod ticker_cache;
+pub mod recorder;
+pub mod simulate;
+pub mod sizing;
+pub mod executor;
+pub mod paper;
+pub mod tracker;
+pub mod notifier;
+pub mod commands;
+pub mod metrics;
+pub mod sink;
+pub mod sqlite;
+pub mod export;
+pub mod live;
 pub mod triangle;
+pub mod backtest;
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use binance::account::Account;
+use binance::api::Binance;
+use binance::config::Config;
+use binance::model::Transaction;
+use log::{error, info, warn};
+
+use crate::config::Configuration;
+use crate::executor::{Executor, Outcome};
+use crate::notifier::Notifier;
+use crate::simulate::Execution;
+use crate::trading_pair::{round_step, TradingPair};
+use crate::tri_pair::{Fill, Profit, Side, TriPair};
+
+// 退出时等待进行中的执行结束的最长时间
+const SHUTDOWN_WAIT: Duration = Duration::from_secs(30);
+// 执行结果队列的容量, 队列满时丢弃新的结果 (日志中已有记录)
+const REPORT_QUEUE: usize = 64;
+
+// 一次实盘执行的结果
+#[derive(Debug, Clone)]
+pub struct LiveReport {
+    pub name: String,
+    pub rev: bool,
+    pub exec: Execution,
+    pub symbols: Vec<String>,         // 与 exec.fills 顺序一致
+    pub slippage: Vec<f64>,           // 成交均价相对 ticker 快照的滑点比例, 正数表示不利
+    pub balances: HashMap<String, f64>, // 执行后三个资产的可用余额
+}
+
+// 一条腿的下单参数
+#[derive(Debug, Clone)]
+struct Leg {
+    symbol: String,
+    side: Side,
+    base: String,
+    quote: String,
+    step: f64,
+    expect: f64, // ticker 快照中的成交价格
+}
+
+// 实盘交易: 通过 binance REST 接口按顺序下三个市价单
+// 第一条腿按 investment_min/max/step 投入 investment_base, 后两条腿使用上一条腿实际得到的数量
+// 每次执行在单独的线程中进行, 同时进行的执行不超过 trading_execution_cap
+pub struct LiveExecutor {
+    account: Account,
+    inflight: Arc<AtomicUsize>,
+    cap: usize,
+    investment_base: String,
+    investment_min: f64,
+    investment_max: f64,
+    investment_step: f64,
+    report_tx: Option<SyncSender<LiveReport>>, // reports() 调用后才创建, 没有接收方时不保存结果
+    notifier: Option<Notifier>,
+}
+
+impl LiveExecutor {
+    pub fn new(config: &Configuration) -> LiveExecutor {
+        LiveExecutor::with_config(config, &Config::default())
+    }
+
+    // 使用指定的 binance 接口配置, 例如测试网或本地 mock 服务
+    pub fn with_config(config: &Configuration, binance_config: &Config) -> LiveExecutor {
+        let account = Account::new_with_config(
+            Some(config.api_key.clone()),
+            Some(config.api_secret.clone()),
+            binance_config,
+        );
+
+        LiveExecutor {
+            account,
+            inflight: Arc::new(AtomicUsize::new(0)),
+            cap: config.trading_execution_cap.max(1) as usize,
+            investment_base: config.investment_base.clone(),
+            investment_min: config.investment_min,
+            investment_max: config.investment_max,
+            investment_step: config.investment_step,
+            report_tx: None,
+            notifier: None,
+        }
+    }
+
+    // 执行结果队列, 只能取一次; 之后的执行结果才会进入队列, 最多保留 REPORT_QUEUE 个未读取的结果
+    pub fn reports(&mut self) -> Option<Receiver<LiveReport>> {
+        if self.report_tx.is_some() {
+            return None
+        }
+        let (report_tx, report_rx) = mpsc::sync_channel(REPORT_QUEUE);
+        self.report_tx = Some(report_tx);
+        Some(report_rx)
+    }
+
+    // 正在进行的执行数量
+    pub fn inflight(&self) -> usize {
+        self.inflight.load(Ordering::SeqCst)
+    }
+
+    // 同步执行三条腿, 任意一条腿失败即停止, 已成交的腿不回滚
+    pub fn execute_blocking(&self, tp: &TriPair, profit: &Profit, pairs: [&TradingPair; 3]) -> Result<LiveReport, String> {
+        let (legs, spend) = self.plan(tp, profit, pairs)?;
+        run_legs(&self.account, tp, profit, legs, spend)
+    }
+
+    fn plan(&self, tp: &TriPair, profit: &Profit, pairs: [&TradingPair; 3]) -> Result<(Vec<Leg>, f64), String> {
+        let mut legs: Vec<Leg> = Vec::with_capacity(3);
+        for (idx, side) in tp.legs(profit.rev).iter() {
+            let k = tp.pairs.iter().position(|p| p == idx).ok_or("invalid tri pair")?;
+            let tick = &profit.tickers[k];
+            legs.push(Leg {
+                symbol: pairs[k].get_symbol(),
+                side: side.clone(),
+                base: pairs[k].base(),
+                quote: pairs[k].quote(),
+                step: pairs[k].get_step(),
+                expect: if *side == Side::SideBuy { tick.ba[0] } else { tick.bb[0] },
+            });
+        }
+
+        // 只投入 investment_base, 即第一条腿付出的资产: 买入时为 quote, 卖出时为 base
+        let start = tp.start_asset(profit.rev);
+        if start != self.investment_base {
+            return Err(format!("{} starts with {}, not {}", tp.name, start, self.investment_base))
+        }
+        let amount = if legs[0].side == Side::SideBuy { profit.amount * legs[0].expect } else { profit.amount };
+        let spend = round_step(amount.min(self.investment_max), self.investment_step);
+        if spend < self.investment_min {
+            return Err(format!("{} amount {} below investment_min", tp.name, spend))
+        }
+        Ok((legs, spend))
+    }
+}
+
+// 数量按 step 的小数位格式化, 避免 0.30000000000000004 这类数量被交易所拒绝
+fn fix_qty(qty: f64, step: f64) -> f64 {
+    let qty = round_step(qty, step);
+    if step <= 0.0 {
+        return qty
+    }
+    let decimals = (-step.log10()).ceil().max(0.0) as usize;
+    format!("{:.*}", decimals, qty).parse().unwrap_or(qty)
+}
+
+// 计算一个订单的成交: 均价, base 数量, 付出, 扣除手续费后得到
+fn to_fill(leg: &Leg, idx: u32, tx: &Transaction) -> Fill {
+    let qty = tx.executed_qty;
+    let quote = tx.cummulative_quote_qty;
+    let received_asset = if leg.side == Side::SideBuy { &leg.base } else { &leg.quote };
+    let commission: f64 = tx.fills.iter().flatten()
+        .filter(|f| &f.commission_asset == received_asset)
+        .map(|f| f.commission)
+        .sum();
+    let price = if qty > 0.0 { quote / qty } else { 0.0 };
+
+    if leg.side == Side::SideBuy {
+        Fill { idx, side: leg.side.clone(), price, qty, spent: quote, received: qty - commission }
+    } else {
+        Fill { idx, side: leg.side.clone(), price, qty, spent: qty, received: quote - commission }
+    }
+}
+
+fn run_legs(account: &Account, tp: &TriPair, profit: &Profit, legs: Vec<Leg>, spend: f64) -> Result<LiveReport, String> {
+    let mut fills: Vec<Fill> = Vec::with_capacity(3);
+    let mut slippage: Vec<f64> = Vec::with_capacity(3);
+    let mut holding = spend;
+
+    for (n, (leg, (idx, _))) in legs.iter().zip(tp.legs(profit.rev).iter()).enumerate() {
+        let result = if leg.side == Side::SideBuy {
+            if n == 0 {
+                account.market_buy(leg.symbol.as_str(), fix_qty(spend / leg.expect, leg.step))
+            } else {
+                account.market_buy_using_quote_quantity(leg.symbol.as_str(), fix_qty(holding, 1e-8))
+            }
+        } else {
+            account.market_sell(leg.symbol.as_str(), fix_qty(holding, leg.step))
+        };
+        let tx = result.map_err(|e| format!("{} leg {} {} failed: {}", tp.name, n + 1, leg.symbol, e))?;
+        let fill = to_fill(leg, *idx, &tx);
+        let slip = if leg.side == Side::SideBuy {
+            (fill.price - leg.expect) / leg.expect
+        } else {
+            (leg.expect - fill.price) / leg.expect
+        };
+        holding = fill.received;
+        fills.push(fill);
+        slippage.push(slip);
+    }
+
+    let mut assets: Vec<String> = legs.iter().flat_map(|l| vec![l.base.clone(), l.quote.clone()]).collect();
+    assets.sort();
+    assets.dedup();
+    let mut balances: HashMap<String, f64> = HashMap::new();
+    match account.get_account() {
+        Ok(info) => {
+            for b in info.balances.iter().filter(|b| assets.contains(&b.asset)) {
+                balances.insert(b.asset.clone(), b.free.parse().unwrap_or(0.0));
+            }
+        }
+        Err(e) => warn!(triangle = tp.name.as_str(), error:% = e; "live trade: get balances failed"),
+    }
+
+    Ok(LiveReport {
+        name: tp.name.clone(),
+        rev: profit.rev,
+        exec: Execution {
+            asset: tp.start_asset(profit.rev),
+            start: fills[0].spent,
+            end: holding,
+            fills,
+        },
+        symbols: legs.iter().map(|l| l.symbol.clone()).collect(),
+        slippage,
+        balances,
+    })
+}
+
+impl Executor for LiveExecutor {
+    // 异步执行, 结果通过 reports() 队列返回, 因此总是返回 None
+    fn execute(&mut self, tp: &TriPair, profit: &Profit, pairs: [&TradingPair; 3]) -> Outcome {
+        if self.inflight.fetch_add(1, Ordering::SeqCst) >= self.cap {
+            self.inflight.fetch_sub(1, Ordering::SeqCst);
+            return Outcome::Skipped
+        }
+        let (legs, spend) = match self.plan(tp, profit, pairs) {
+            Ok(plan) => plan,
+            Err(e) => {
+                self.inflight.fetch_sub(1, Ordering::SeqCst);
+                warn!(triangle = tp.name.as_str(), error:% = e; "live trade skipped");
+                return Outcome::Skipped
+            }
+        };
+
+        let account = self.account.clone();
+        let inflight = self.inflight.clone();
+        let report_tx = self.report_tx.clone();
+        let notifier = self.notifier.clone();
+        let tp = tp.clone();
+        let profit = profit.clone();
+        thread::spawn(move || {
+            let result = run_legs(&account, &tp, &profit, legs, spend);
+            inflight.fetch_sub(1, Ordering::SeqCst);
+            match result {
+                Ok(report) => {
+                    info!(target: "trade", triangle = report.name.as_str(), rev = report.rev, spent = report.exec.start,
+                        received = report.exec.end, asset = report.exec.asset.as_str(), pnl = report.exec.pnl(),
+                        slippage:? = report.slippage, balances:? = report.balances; "live trade executed");
+                    if let Some(notifier) = &notifier {
+                        notifier.notify(&format!("live trade {} rev={}: spent {} {} got {} {}, pnl {}",
+                            report.name, report.rev, report.exec.start, report.exec.asset, report.exec.end,
+                            report.exec.asset, report.exec.pnl()));
+                    }
+                    if let Some(report_tx) = &report_tx {
+                        let _ = report_tx.try_send(report);
+                    }
+                }
+                Err(e) => {
+                    error!(target: "trade", triangle = tp.name.as_str(), error:% = e; "live trade failed");
+                    if let Some(notifier) = &notifier {
+                        notifier.notify(&format!("live trade {} failed: {}", tp.name, e));
+                    }
+                }
+            }
+        });
+
+        Outcome::Submitted
+    }
+
+    fn set_notifier(&mut self, notifier: Notifier) {
+        self.notifier = Some(notifier);
+    }
+
+    // 等待进行中的执行结束, 已下单的腿不能中途放弃; 超过 SHUTDOWN_WAIT 仍未结束时直接退出
+    fn shutdown(&mut self) {
+        let start = Instant::now();
+        while self.inflight() > 0 {
+            if start.elapsed() >= SHUTDOWN_WAIT {
+                warn!(inflight = self.inflight(); "live trade: executions still running, exit anyway");
+                return
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::kv::{self, Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record};
+
+pub use crate::config::{parse_level, LogFormat};
+use crate::config::Configuration;
+use crate::error::Result;
+
+// 输出到 stdout, 级别由 log::max_level 控制, 运行时可通过 set_level 修改
+#[derive(Debug)]
+pub struct Logger {
+    format: LogFormat,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return
+        }
+        let line = format_record(record, self.format, Utc::now());
+        let _ = writeln!(io::stdout().lock(), "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = io::stdout().flush();
+    }
+}
+
+// 按 log_format 和 log_level 安装日志, 不设置时为 text 和 info; 已经安装过时只修改级别
+pub fn init(config: &Configuration) -> Result<()> {
+    let format = LogFormat::parse(config.log_format.as_deref().unwrap_or("text"))?;
+    let level = parse_level(config.log_level.as_deref().unwrap_or("info"))?;
+    let _ = log::set_boxed_logger(Box::new(Logger { format }));
+    log::set_max_level(level);
+    Ok(())
+}
+
+// 运行时修改日志级别, 例如打开 calc_profit 的调试输出
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+pub fn level() -> LevelFilter {
+    log::max_level()
+}
+
+// 一条日志的输出, 结构化字段 (key = value) 在 json 中为同级的字段, 在 text 中附加在消息后
+pub fn format_record(record: &Record, format: LogFormat, now: DateTime<Utc>) -> String {
+    let ts = now.to_rfc3339_opts(SecondsFormat::Millis, true);
+    let mut out = String::new();
+    match format {
+        LogFormat::Text => {
+            let _ = write!(out, "{} {:<5} {}: {}", ts, record.level(), record.target(), record.args());
+        }
+        LogFormat::Json => {
+            let _ = write!(out, "{{\"ts\":{},\"level\":{},\"target\":{},\"msg\":{}",
+                json_str(&ts), json_str(record.level().as_str()), json_str(record.target()), json_str(&record.args().to_string()));
+        }
+    }
+    let _ = record.key_values().visit(&mut Fields { format, out: &mut out });
+    if format == LogFormat::Json {
+        out.push('}');
+    }
+    out
+}
+
+fn json_str(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_default()
+}
+
+struct Fields<'a> {
+    format: LogFormat,
+    out: &'a mut String,
+}
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> std::result::Result<(), kv::Error> {
+        match self.format {
+            LogFormat::Text => {
+                let s = value.to_string();
+                if s.is_empty() || s.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+                    let _ = write!(self.out, " {}={:?}", key, s);
+                } else {
+                    let _ = write!(self.out, " {}={}", key, s);
+                }
+            }
+            LogFormat::Json => {
+                let _ = write!(self.out, ",{}:{}", json_str(key.as_str()), json_value(&value));
+            }
+        }
+        Ok(())
+    }
+}
+
+// 布尔和数字保持原类型, 其他值 (以及 NaN/inf) 输出为字符串
+fn json_value(value: &Value) -> String {
+    if let Some(b) = value.to_bool() {
+        return b.to_string()
+    }
+    if let Some(n) = value.to_u64() {
+        return n.to_string()
+    }
+    if let Some(n) = value.to_i64() {
+        return n.to_string()
+    }
+    if let Some(x) = value.to_f64().filter(|x| x.is_finite()) {
+        return serde_json::to_string(&x).unwrap_or_default()
+    }
+    json_str(&value.to_string())
+}
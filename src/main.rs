@@ -1,8 +1,74 @@
 use std::env;
+use std::process;
+use triangle::config::{split_config_args, Configuration};
+use triangle::error::{Error, Result};
+use triangle::logger;
+use triangle::notifier::Notifier;
+use triangle::recorder::{TickerRecorder, TickerReplay};
+use triangle::shutdown::Shutdown;
+use triangle::ticker_cache::BinanceBookTicker;
 use triangle::triangle::TriAngleArb;
 
+// triangle <config>                        监听 binance, 打印套利机会
+// triangle <config> record <file>          同上, 并录制 ticker 到文件
+// triangle <config> replay <file> [speed]  从录制文件回放, speed 默认 1.0, 0 为不等待
+// triangle <config> check-config           检查配置, 有错误时退出码为 1
+// <config> 之后可以用 --config <file> 叠加多个配置文件, 例如 triangle config/sample_config.toml --config prod.local.yaml
+// 启动前检查配置, 有错误时不启动
+// Ctrl-C / SIGTERM 时扫描循环退出, 等待进行中的交易结束并刷新录制文件后退出进程
 fn main() {
   let args: Vec<String> = env::args().collect();
-  let mut ta = TriAngleArb::new(&args[1]);
-  ta.start();
+  if let Err(e) = run(&args) {
+    eprintln!("{}", e);
+    process::exit(1);
+  }
+  log::info!("scanner stopped");
+}
+
+fn run(args: &[String]) -> Result<()> {
+  let (paths, args) = split_config_args(args.get(1..).unwrap_or_default())
+    .map_err(|_| Error::Config("usage: triangle <config> [--config <file>]... [record|replay <file>|check-config]".to_string()))?;
+  let paths: Vec<&str> = paths.iter().map(|x| x.as_str()).collect();
+  let config_path = paths.join(" ");
+  let file = || args.get(1).ok_or_else(|| Error::Config("missing record file".to_string()));
+  let config = Configuration::from_paths(&paths)?;
+  let validation = config.validate();
+  if args.first().map(|x| x.as_str()) == Some("check-config") {
+    println!("{}", validation);
+    return if validation.is_ok() { Ok(()) } else { Err(Error::Config(format!("{}: invalid configuration", config_path))) }
+  }
+  if !validation.is_ok() {
+    return Err(Error::Config(format!("{}: invalid configuration\n{}", config_path, validation)))
+  }
+  logger::init(&config)?;
+  for w in &validation.warnings {
+    log::warn!(target: "config", "{}", w);
+  }
+  let shutdown = Shutdown::on_signal().map_err(|e| Error::Io(std::io::Error::other(e)))?;
+  match args.first().map(|x| x.as_str()) {
+    Some("record") => {
+      let notifier = Notifier::from_config(&config);
+      let mut ta = TriAngleArb::from_config(&config)?;
+      ta.set_shutdown(shutdown);
+      ta.set_notifier(notifier.clone());
+      let mut source = TickerRecorder::new(BinanceBookTicker::new().with_notifier(notifier), file()?, ta.pairs())?;
+      ta.start_with(&mut source)?;
+    }
+    Some("replay") => {
+      let speed: f64 = match args.get(2) {
+        Some(x) => x.parse().map_err(|_| Error::Config(format!("invalid replay speed: {}", x)))?,
+        None => 1.0,
+      };
+      let mut source = TickerReplay::open(file()?, speed)?;
+      let mut ta = TriAngleArb::with_pairs(&config, source.pairs(&config));
+      ta.set_shutdown(shutdown);
+      ta.start_with(&mut source)?;
+    }
+    _ => {
+      let mut ta = TriAngleArb::from_config(&config)?;
+      ta.set_shutdown(shutdown);
+      ta.start()?;
+    }
+  }
+  Ok(())
 }
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use crate::tri_pair::Ticker;
+
+// 行情数据源
+// 三角套利扫描 (TriAngleArb) 只依赖 Ticker 队列, 不关心 ticker 来自 binance ws、回放文件还是模拟器
+pub trait MarketDataSource {
+    // 订阅交易对的最优价格
+    // symbol_id_map: 交易所 symbol (例如 ETHBTC) 与 TradingPair idx 的对应关系
+    // 返回的队列在数据源结束时断开 (sender 被 drop), 扫描循环随之退出
+    fn subscribe(&mut self, symbol_id_map: HashMap<String, u32>) -> Receiver<Ticker>;
+}
+
+// ws 重连的退避时间: 从 min 开始每次失败翻倍, 不超过 max; 连接收到数据后 reset
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl Backoff {
+    pub fn new(min: Duration, max: Duration) -> Backoff {
+        Backoff { min, max, next: min }
+    }
+
+    // 本次应等待的时间
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.next = self.min;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Backoff {
+        Backoff::new(Duration::from_secs(1), Duration::from_secs(60))
+    }
+}
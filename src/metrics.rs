@@ -0,0 +1,188 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::warn;
+
+// tick 到计算完成的延迟直方图的桶上限, 秒
+const LATENCY_BUCKETS: [f64; 12] = [0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+// 读取请求的超时时间, 避免异常连接阻塞服务线程
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default)]
+struct Histogram {
+    counts: [u64; LATENCY_BUCKETS.len()], // 每个桶 (不累计) 的数量, 超过最大桶的只计入 count
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, v: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|b| v <= *b) {
+            self.counts[i] += 1;
+        }
+        self.sum += v;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    tickers: HashMap<String, u64>, // 交易对 -> 收到的 ticker 数量
+    ratio: HashMap<String, f64>, // 三角组合 -> 最近一次计算的收益比例
+    latency: Histogram,
+}
+
+// 扫描循环的运行指标, 以 Prometheus 文本格式输出; 可在多个线程间 Clone 共享
+#[derive(Debug, Default, Clone)]
+pub struct Metrics {
+    reconnects: Arc<AtomicU64>,
+    evaluated: Arc<AtomicU64>,
+    opportunities: Arc<AtomicU64>,
+    state: Arc<Mutex<State>>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    // 收到交易对的 ticker
+    pub fn ticker_received(&self, symbol: &str) {
+        let mut state = self.state.lock().unwrap();
+        match state.tickers.get_mut(symbol) {
+            Some(n) => *n += 1,
+            None => {
+                state.tickers.insert(symbol.to_string(), 1);
+            }
+        }
+    }
+
+    // ws 断开或连接失败后重连
+    pub fn ws_reconnected(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // 一个 ticker 从接收到关联的三角组合计算完成的时间, 秒
+    pub fn observe_latency(&self, seconds: f64) {
+        self.state.lock().unwrap().latency.observe(seconds);
+    }
+
+    // 计算了一个三角组合的收益, above: 收益比例达到 trading_profit_threshold
+    pub fn triangle_evaluated(&self, name: &str, ratio: f64, above: bool) {
+        self.evaluated.fetch_add(1, Ordering::Relaxed);
+        if above {
+            self.opportunities.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut state = self.state.lock().unwrap();
+        match state.ratio.get_mut(name) {
+            Some(r) => *r = ratio,
+            None => {
+                state.ratio.insert(name.to_string(), ratio);
+            }
+        }
+    }
+
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects.load(Ordering::Relaxed)
+    }
+
+    pub fn evaluated(&self) -> u64 {
+        self.evaluated.load(Ordering::Relaxed)
+    }
+
+    pub fn opportunities(&self) -> u64 {
+        self.opportunities.load(Ordering::Relaxed)
+    }
+
+    // Prometheus 文本格式 (0.0.4), 标签按名称排序输出
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP triangle_tickers_received_total Tickers received per symbol.");
+        let _ = writeln!(out, "# TYPE triangle_tickers_received_total counter");
+        for (symbol, n) in state.tickers.iter().collect::<BTreeMap<_, _>>() {
+            let _ = writeln!(out, "triangle_tickers_received_total{{symbol=\"{}\"}} {}", escape(symbol), n);
+        }
+
+        let _ = writeln!(out, "# HELP triangle_ws_reconnects_total Websocket reconnects after errors.");
+        let _ = writeln!(out, "# TYPE triangle_ws_reconnects_total counter");
+        let _ = writeln!(out, "triangle_ws_reconnects_total {}", self.reconnects());
+
+        let _ = writeln!(out, "# HELP triangle_tick_latency_seconds Time from ticker receipt to evaluation of its triangles.");
+        let _ = writeln!(out, "# TYPE triangle_tick_latency_seconds histogram");
+        let mut cumulative = 0;
+        for (le, n) in LATENCY_BUCKETS.iter().zip(state.latency.counts.iter()) {
+            cumulative += n;
+            let _ = writeln!(out, "triangle_tick_latency_seconds_bucket{{le=\"{}\"}} {}", le, cumulative);
+        }
+        let _ = writeln!(out, "triangle_tick_latency_seconds_bucket{{le=\"+Inf\"}} {}", state.latency.count);
+        let _ = writeln!(out, "triangle_tick_latency_seconds_sum {}", state.latency.sum);
+        let _ = writeln!(out, "triangle_tick_latency_seconds_count {}", state.latency.count);
+
+        let _ = writeln!(out, "# HELP triangle_triangles_evaluated_total Triangle profit evaluations.");
+        let _ = writeln!(out, "# TYPE triangle_triangles_evaluated_total counter");
+        let _ = writeln!(out, "triangle_triangles_evaluated_total {}", self.evaluated());
+
+        let _ = writeln!(out, "# HELP triangle_opportunities_total Evaluations whose ratio reached trading_profit_threshold.");
+        let _ = writeln!(out, "# TYPE triangle_opportunities_total counter");
+        let _ = writeln!(out, "triangle_opportunities_total {}", self.opportunities());
+
+        let _ = writeln!(out, "# HELP triangle_ratio Profit ratio of the latest evaluation per triangle.");
+        let _ = writeln!(out, "# TYPE triangle_ratio gauge");
+        for (name, ratio) in state.ratio.iter().collect::<BTreeMap<_, _>>() {
+            let _ = writeln!(out, "triangle_ratio{{triangle=\"{}\"}} {}", escape(name), ratio);
+        }
+        out
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// 在 addr 上提供 GET /metrics, 一个线程顺序处理请求; 返回实际监听的地址 (端口为 0 时由系统分配)
+pub fn serve(addr: &str, metrics: Metrics) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(e) = handle(stream, &metrics) {
+                        warn!(error:% = e; "metrics request failed");
+                    }
+                }
+                Err(e) => warn!(error:% = e; "metrics accept failed"),
+            }
+        }
+    });
+    Ok(local)
+}
+
+fn handle(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // 读完请求头, 不关心内容
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body)?;
+    stream.flush()
+}
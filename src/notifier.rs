@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{error, warn};
+use telegram_bot::{Api, SendMessage, UserId};
+
+use crate::config::Configuration;
+
+// 每分钟最多发送的消息数量, 配置 telegram_rate_per_minute 可修改
+const DEFAULT_RATE_PER_MINUTE: usize = 20;
+// 单条消息的发送超时
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 滑动窗口限流: 任意 per 时间内最多 limit 条
+#[derive(Debug)]
+struct RateLimiter {
+    limit: usize,
+    per: Duration,
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    fn allow(&mut self, now: Instant) -> bool {
+        while self.sent.front().is_some_and(|t| now.duration_since(*t) >= self.per) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.limit {
+            return false
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+// Telegram 通知: 超过 telegram_notify_ratio 的机会、执行结果和 ws 连接失败
+// 消息在后台线程中发送, 不阻塞扫描循环; 超过频率限制的消息丢弃并计数, 下一条消息附带丢弃的数量
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    tx: Sender<String>,
+    limiter: Arc<Mutex<RateLimiter>>,
+    suppressed: Arc<AtomicUsize>,
+    pending: Arc<AtomicUsize>, // 丢弃后尚未在消息中报告的数量
+    min_ratio: f64, // 比例, 配置中为百分比
+}
+
+impl Notifier {
+    // telegram_token 和 telegram_user_id 都设置时才发送通知
+    pub fn from_config(config: &Configuration) -> Option<Notifier> {
+        if config.telegram_token.is_empty() || config.telegram_user_id == 0 {
+            return None
+        }
+        let rate = config.telegram_rate_per_minute.unwrap_or(DEFAULT_RATE_PER_MINUTE);
+        let ratio = config.telegram_notify_ratio.unwrap_or(config.trading_profit_threshold);
+        Some(Notifier::spawn(&config.telegram_token, config.telegram_user_id, rate, ratio / 100.0))
+    }
+
+    // 启动发送线程, Bot API 地址可通过 TELEGRAM_API_URL 环境变量修改 (测试时指向本地服务)
+    pub fn spawn(token: &str, user_id: i64, rate_per_minute: usize, min_ratio: f64) -> Notifier {
+        let (tx, rx): (Sender<String>, Receiver<String>) = mpsc::channel();
+        let token = token.to_string();
+        thread::spawn(move || send_loop(&token, user_id, rx));
+
+        Notifier {
+            tx,
+            limiter: Arc::new(Mutex::new(RateLimiter {
+                limit: rate_per_minute.max(1),
+                per: Duration::from_secs(60),
+                sent: VecDeque::new(),
+            })),
+            suppressed: Arc::new(AtomicUsize::new(0)),
+            pending: Arc::new(AtomicUsize::new(0)),
+            min_ratio,
+        }
+    }
+
+    // 机会的收益比例达到该值时通知
+    pub fn min_ratio(&self) -> f64 {
+        self.min_ratio
+    }
+
+    // 因频率限制丢弃的消息总数
+    pub fn suppressed(&self) -> usize {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+
+    // 回复命令, 不受频率限制
+    pub fn reply(&self, text: &str) -> bool {
+        self.tx.send(text.to_string()).is_ok()
+    }
+
+    // 发送一条消息, 超过频率限制时丢弃, 返回是否发送
+    pub fn notify(&self, text: &str) -> bool {
+        if !self.limiter.lock().unwrap().allow(Instant::now()) {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            self.pending.fetch_add(1, Ordering::Relaxed);
+            return false
+        }
+        let dropped = self.pending.swap(0, Ordering::Relaxed);
+        let text = if dropped > 0 {
+            format!("{}\n({} messages suppressed by rate limit)", text, dropped)
+        } else {
+            text.to_string()
+        };
+        self.tx.send(text).is_ok()
+    }
+}
+
+fn send_loop(token: &str, user_id: i64, rx: Receiver<String>) {
+    let mut rt = match tokio::runtime::Builder::new().basic_scheduler().enable_all().build() {
+        Ok(rt) => rt,
+        Err(e) => {
+            error!(error:% = e; "telegram notifier disabled");
+            return
+        }
+    };
+    let api = Api::new(token);
+    let chat = UserId::new(user_id);
+    for text in rx {
+        match rt.block_on(api.send_timeout(SendMessage::new(chat, text), SEND_TIMEOUT)) {
+            Ok(Some(_)) => {}
+            Ok(None) => warn!("telegram send timeout"),
+            Err(e) => warn!(error:% = e; "telegram send failed"),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use log::info;
+
+use crate::config::Configuration;
+use crate::executor::{Executor, Outcome};
+use crate::simulate::{simulate, Execution};
+use crate::trading_pair::{round_step, TradingPair};
+use crate::tri_pair::{Profit, Side, TriPair};
+
+// 模拟交易: 按盘口价格成交三条腿, 只更新虚拟账本, 不下单
+#[derive(Debug, Clone)]
+pub struct PaperExecutor {
+    balances: HashMap<String, f64>,
+    investment_base: String,
+    investment_min: f64,
+    investment_max: f64,
+    investment_step: f64,
+    executions: u64,
+}
+
+impl PaperExecutor {
+    pub fn new(config: &Configuration) -> PaperExecutor {
+        let balances = match &config.paper_balances {
+            Some(balances) => balances.clone(),
+            None => vec![(config.investment_base.clone(), config.investment_max)].into_iter().collect(),
+        };
+
+        PaperExecutor {
+            balances,
+            investment_base: config.investment_base.clone(),
+            investment_min: config.investment_min,
+            investment_max: config.investment_max,
+            investment_step: config.investment_step,
+            executions: 0,
+        }
+    }
+
+    pub fn balance(&self, asset: &str) -> f64 {
+        *self.balances.get(asset).unwrap_or(&0.0)
+    }
+
+    pub fn balances(&self) -> &HashMap<String, f64> {
+        &self.balances
+    }
+
+    pub fn executions(&self) -> u64 {
+        self.executions
+    }
+
+    fn text_balances(&self) -> String {
+        let mut assets: Vec<&String> = self.balances.keys().collect();
+        assets.sort();
+        assets.iter().map(|a| format!("{}={}", a, self.balances[*a])).collect::<Vec<String>>().join(" ")
+    }
+}
+
+impl Executor for PaperExecutor {
+    fn execute(&mut self, tp: &TriPair, profit: &Profit, pairs: [&TradingPair; 3]) -> Outcome {
+        self.trade(tp, profit, pairs).map_or(Outcome::Skipped, Outcome::Executed)
+    }
+}
+
+impl PaperExecutor {
+    // 按盘口价格成交并更新账本, 余额不足或数量过小时返回 None
+    fn trade(&mut self, tp: &TriPair, profit: &Profit, pairs: [&TradingPair; 3]) -> Option<Execution> {
+        // 投入的是第一条腿付出的资产: 买入时为该交易对的 quote, 卖出时为 base
+        let asset = tp.start_asset(profit.rev);
+        let tickers = [&profit.tickers[0], &profit.tickers[1], &profit.tickers[2]];
+        // 每个起始资产对应的第一条腿 base 数量
+        let unit = tp.start_qty(profit.rev, 1.0, tickers)?;
+
+        let mut spend = (profit.amount / unit).min(self.balance(&asset));
+        if asset == self.investment_base {
+            spend = round_step(spend.min(self.investment_max), self.investment_step);
+            if spend < self.investment_min {
+                return None
+            }
+        }
+        let mut sized = profit.clone();
+        sized.amount = spend * unit;
+
+        let exec = simulate(tp, &sized, pairs)?;
+        for fill in &exec.fills {
+            let pair = pairs[tp.pairs.iter().position(|p| *p == fill.idx)?];
+            let (pay, get) = if fill.side == Side::SideBuy {
+                (pair.quote(), pair.base())
+            } else {
+                (pair.base(), pair.quote())
+            };
+            *self.balances.entry(pay).or_insert(0.0) -= fill.spent;
+            *self.balances.entry(get).or_insert(0.0) += fill.received;
+        }
+        self.executions += 1;
+
+        info!(target: "trade", execution = self.executions, triangle = tp.name.as_str(), rev = profit.rev,
+            spent = exec.start, received = exec.end, asset = exec.asset.as_str(), pnl = exec.pnl(),
+            balances = self.text_balances().as_str(); "paper trade executed");
+        Some(exec)
+    }
+}
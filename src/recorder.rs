@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::prelude::Local;
+use log::error;
+
+use crate::config::Configuration;
+use crate::market_data::MarketDataSource;
+use crate::trading_pair::{PairFilters, TradingPair};
+use crate::tri_pair::Ticker;
+
+// ticker 录制文件格式, 每行一条记录, 空格分隔:
+//   P <symbol> <base> <quote> <step> [<min qty> <max qty> <min notional>]   交易对, 位于文件头部
+//   T <recv ms> <symbol> <bid> <bid qty> <ask> <ask qty>         ticker
+// 交易对信息使回放不需要访问交易所接口
+
+// 录制: 包装一个数据源, 把收到的每个 ticker 写入文件, 再原样转发给扫描循环
+// 录制线程可能阻塞在数据源上, 因此 drop 时由扫描线程刷新文件, 正常退出时不丢失数据
+pub struct TickerRecorder<S: MarketDataSource> {
+    inner: S,
+    writer: Arc<Mutex<BufWriter<File>>>,
+    subscribed: bool,
+    pairs: Vec<TradingPair>,
+}
+
+impl<S: MarketDataSource> TickerRecorder<S> {
+    pub fn new<P: AsRef<Path>>(inner: S, path: P, pairs: Vec<TradingPair>) -> io::Result<TickerRecorder<S>> {
+        let file = File::create(path)?;
+        Ok(TickerRecorder {
+            inner,
+            writer: Arc::new(Mutex::new(BufWriter::new(file))),
+            subscribed: false,
+            pairs,
+        })
+    }
+}
+
+impl<S: MarketDataSource> MarketDataSource for TickerRecorder<S> {
+    fn subscribe(&mut self, symbol_id_map: HashMap<String, u32>) -> Receiver<Ticker> {
+        assert!(!self.subscribed, "recorder already subscribed");
+        self.subscribed = true;
+        let id_symbol: HashMap<u32, String> = symbol_id_map.iter().map(|(s, id)| (*id, s.clone())).collect();
+        let in_rx = self.inner.subscribe(symbol_id_map);
+        let (out_tx, out_rx): (Sender<Ticker>, Receiver<Ticker>) = mpsc::channel();
+
+        {
+            let mut writer = self.writer.lock().unwrap();
+            for pair in &self.pairs {
+                let f = &pair.filters;
+                if let Err(e) = writeln!(writer, "P {} {} {} {} {} {} {}",
+                        pair.get_symbol(), pair.get_base_asset(), pair.get_quote_asset(), pair.get_step(),
+                        f.min_qty, f.max_qty, f.min_notional) {
+                    error!(error:% = e; "recorder write pair failed");
+                }
+            }
+        }
+
+        let writer = self.writer.clone();
+        thread::spawn(move || {
+            let mut last_flush = Local::now().timestamp_millis();
+            for tick in in_rx {
+                let now = Local::now().timestamp_millis();
+                let mut writer = writer.lock().unwrap();
+                if let Some(symbol) = id_symbol.get(&tick.idx) {
+                    if let Err(e) = writeln!(writer, "T {} {} {} {} {} {}",
+                            now, symbol, tick.bb[0], tick.bb[1], tick.ba[0], tick.ba[1]) {
+                        error!(error:% = e; "recorder write ticker failed");
+                    }
+                }
+                // 至多每秒刷新一次, 进程被中断时最多丢失一秒的数据
+                if now - last_flush >= 1000 {
+                    let _ = writer.flush();
+                    last_flush = now;
+                }
+                drop(writer);
+                if out_tx.send(tick).is_err() {
+                    break;
+                }
+            }
+            let _ = writer.lock().unwrap().flush();
+        });
+
+        out_rx
+    }
+}
+
+impl<S: MarketDataSource> Drop for TickerRecorder<S> {
+    fn drop(&mut self) {
+        if let Ok(mut writer) = self.writer.lock() {
+            let _ = writer.flush();
+        }
+    }
+}
+
+// 回放: 从录制文件读取 ticker, 按原始时间间隔 (可加速) 发送给扫描循环, 文件结束时队列断开
+pub struct TickerReplay {
+    pairs: Vec<(String, String, String, PairFilters)>, // symbol base quote filters
+    first: Option<(i64, String, Ticker)>,
+    lines: Option<Lines<BufReader<File>>>,
+    speed: f64,
+}
+
+// 解析交易对的过滤器: step, 以及可选的 min qty, max qty, min notional
+fn parse_filters(fields: &[&str]) -> Option<PairFilters> {
+    let v: Vec<f64> = fields.iter().map(|x| x.parse().ok()).collect::<Option<Vec<f64>>>()?;
+    let mut filters = PairFilters { step: v[0], ..PairFilters::default() };
+    if v.len() == 4 {
+        filters.min_qty = v[1];
+        filters.max_qty = v[2];
+        filters.min_notional = v[3];
+    }
+    Some(filters)
+}
+
+// 解析一行 ticker 记录, 返回 (接收时间, symbol, ticker), ticker.idx 为 0
+// 回放的 ticker 不带接收时间 (ts 为 0), 不按 trading_age_threshold 检查是否过期
+fn parse_ticker_line(fields: &[&str]) -> Option<(i64, String, Ticker)> {
+    if fields.len() != 7 || fields[0] != "T" {
+        return None
+    }
+    let ts: i64 = fields[1].parse().ok()?;
+    let tick = Ticker {
+        idx: 0,
+        bb: [fields[3].parse().ok()?, fields[4].parse().ok()?],
+        ba: [fields[5].parse().ok()?, fields[6].parse().ok()?],
+        ..Ticker::default()
+    };
+    Some((ts, fields[2].to_string(), tick))
+}
+
+impl TickerReplay {
+    // speed: 1.0 按原始速度回放, 10.0 为 10 倍速, <= 0 不等待, 尽快回放
+    pub fn open<P: AsRef<Path>>(path: P, speed: f64) -> io::Result<TickerReplay> {
+        let file = File::open(path)?;
+        let mut lines = BufReader::new(file).lines();
+        let mut pairs = Vec::new();
+        let mut first = None;
+
+        // 读取文件头部的交易对, 直到第一条 ticker
+        for line in &mut lines {
+            let line = line?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if (fields.len() == 5 || fields.len() == 8) && fields[0] == "P" {
+                if let Some(filters) = parse_filters(&fields[4..]) {
+                    pairs.push((fields[1].to_string(), fields[2].to_string(), fields[3].to_string(), filters));
+                }
+            } else if let Some(rec) = parse_ticker_line(&fields) {
+                first = Some(rec);
+                break
+            }
+        }
+
+        Ok(TickerReplay {
+            pairs,
+            first,
+            lines: Some(lines),
+            speed,
+        })
+    }
+
+    // 录制文件中的交易对, idx 从 1 开始, 手续费按配置计算
+    pub fn pairs(&self, config: &Configuration) -> Vec<TradingPair> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .map(|(i, (symbol, base, quote, filters))| TradingPair::new(
+                i as u32 + 1,
+                symbol.clone(),
+                base.clone(),
+                quote.clone(),
+                filters.step,
+                config.pair_fee(symbol),
+            ).with_filters(filters.clone()))
+            .collect()
+    }
+
+    // 按顺序读取所有 ticker 记录: (接收时间 ms, symbol, ticker), ticker.idx 为 0
+    // 只能读取一次, 与 subscribe 互斥
+    pub fn records(&mut self) -> impl Iterator<Item = (i64, String, Ticker)> + Send {
+        let lines = self.lines.take().expect("replay already consumed");
+        self.first.take().into_iter().chain(lines.filter_map(|line| {
+            let line = line.ok()?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            parse_ticker_line(&fields)
+        }))
+    }
+}
+
+impl MarketDataSource for TickerReplay {
+    fn subscribe(&mut self, symbol_id_map: HashMap<String, u32>) -> Receiver<Ticker> {
+        let (in_tx, in_rx): (Sender<Ticker>, Receiver<Ticker>) = mpsc::channel();
+        let records = self.records();
+        let speed = self.speed;
+
+        thread::spawn(move || {
+            let mut prev_ts: Option<i64> = None;
+
+            for (ts, symbol, mut tick) in records {
+                let id = match symbol_id_map.get(&symbol) {
+                    Some(id) => *id,
+                    None => continue,
+                };
+                if speed > 0.0 {
+                    if let Some(prev) = prev_ts {
+                        if ts > prev {
+                            thread::sleep(Duration::from_micros(((ts - prev) as f64 * 1000.0 / speed) as u64));
+                        }
+                    }
+                    prev_ts = Some(ts);
+                }
+                tick.idx = id;
+                if in_tx.send(tick).is_err() {
+                    break;
+                }
+            }
+        });
+
+        in_rx
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use log::info;
+
+// 退出标志, 收到 SIGINT / SIGTERM 或调用 trigger 时置位
+// 扫描循环每次等待 ticker 超时后检查, 置位后退出循环, 等待执行中的交易结束并刷新录制文件
+#[derive(Debug, Default, Clone)]
+pub struct Shutdown {
+    flag: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        Shutdown::default()
+    }
+
+    // 安装 SIGINT / SIGTERM 处理, 一个进程只能安装一次
+    pub fn on_signal() -> Result<Shutdown, ctrlc::Error> {
+        let shutdown = Shutdown::new();
+        let handle = shutdown.clone();
+        ctrlc::set_handler(move || {
+            info!("signal received, shutting down");
+            handle.trigger();
+        })?;
+        Ok(shutdown)
+    }
+
+    pub fn trigger(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+}
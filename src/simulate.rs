@@ -0,0 +1,33 @@
+use crate::trading_pair::TradingPair;
+use crate::tri_pair::{Fill, Profit, Side, TriPair};
+
+// 三条腿的模拟执行结果, 起始资产与结束资产相同
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub asset: String,
+    pub start: f64, // 第一条腿投入的数量
+    pub end: f64,   // 最后一条腿收回的数量
+    pub fills: Vec<Fill>,
+}
+
+impl Execution {
+    pub fn pnl(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+// 以 Profit 中的 ticker 快照模拟成交三条腿, 见 TriPair::fill_legs
+// pairs 与 tp.pairs 顺序一致, 用于确定起始资产: 第一条腿买入时为 quote, 卖出时为 base
+pub fn simulate(tp: &TriPair, profit: &Profit, pairs: [&TradingPair; 3]) -> Option<Execution> {
+    let tickers = [&profit.tickers[0], &profit.tickers[1], &profit.tickers[2]];
+    let fills = tp.fill_legs(profit.rev, profit.amount, tickers)?;
+    let k = tp.pairs.iter().position(|p| *p == fills[0].idx)?;
+    let asset = if fills[0].side == Side::SideBuy { pairs[k].quote() } else { pairs[k].base() };
+
+    Some(Execution {
+        asset,
+        start: fills[0].spent,
+        end: fills[2].received,
+        fills,
+    })
+}
@@ -0,0 +1,50 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use crate::config::Configuration;
+use crate::error::{Error, Result};
+use crate::export::ExportSink;
+use crate::sqlite::SqliteSink;
+use crate::tri_pair::Profit;
+
+// 扫描循环发现的机会的存储
+// symbols 为三条腿的交易对, 与 profit.tickers 顺序一致
+pub trait OpportunitySink: Send + Debug {
+    // 每个机会开始时调用一次
+    fn record(&mut self, profit: &Profit, symbols: [&str; 3]) -> Result<()>;
+
+    // 每次计算出收益为正且超过 trading_profit_threshold 时调用, 机会持续期间每个 ticker 一次; 默认不处理
+    fn record_profit(&mut self, _profit: &Profit, _symbols: [&str; 3]) -> Result<()> {
+        Ok(())
+    }
+
+    // 扫描循环退出时调用
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// 扫描循环与其他线程共享 sink, TriAngleArb 需要 Clone
+pub type SharedSink = Arc<Mutex<dyn OpportunitySink>>;
+
+// 按配置创建 sink, 未配置时为空
+// 每个 sink 单独创建, 任意一个失败时返回所有失败的 sink 及原因, 由调用方停止启动
+pub fn from_config(config: &Configuration) -> Result<Vec<SharedSink>> {
+    let mut sinks: Vec<SharedSink> = vec![];
+    let mut errors: Vec<String> = vec![];
+    if let Some(path) = &config.sqlite_path {
+        match SqliteSink::open(path) {
+            Ok(sink) => sinks.push(Arc::new(Mutex::new(sink))),
+            Err(e) => errors.push(format!("sqlite_path {}: {}", path, e)),
+        }
+    }
+    match ExportSink::from_config(config) {
+        Ok(Some(export)) => sinks.push(Arc::new(Mutex::new(export))),
+        Ok(None) => {}
+        Err(e) => errors.push(format!("export_dir {}: {}", config.export_dir.as_deref().unwrap_or(""), e)),
+    }
+    if !errors.is_empty() {
+        return Err(Error::Storage(errors.join("; ")))
+    }
+    Ok(sinks)
+}
@@ -0,0 +1,101 @@
+use crate::config::Configuration;
+use crate::trading_pair::TradingPair;
+use crate::tri_pair::{Ticker, TriPair};
+
+// 投入量的搜索上限, 避免 investment_step 配置过小
+const MAX_CANDIDATES: usize = 10000;
+
+// 按投资范围选择的成交量, spend 和 profit 以 investment_base 计
+#[derive(Debug, Default, Clone)]
+pub struct Sized {
+    pub spend: f64,  // 实际投入, 按第一条腿取整后换算
+    pub amount: f64, // 第一条腿成交的 base 数量
+    pub profit: f64,
+}
+
+// 在 investment_min..=investment_max 范围内按 investment_step 搜索收益最大的投入量
+#[derive(Debug, Clone)]
+pub struct Sizing {
+    base: String,
+    min: f64,
+    max: f64,
+    step: f64,
+}
+
+impl Sizing {
+    pub fn new(config: &Configuration) -> Sizing {
+        Sizing {
+            base: config.investment_base.clone(),
+            min: config.investment_min,
+            max: config.investment_max,
+            step: config.investment_step,
+        }
+    }
+
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    // investment_base 是三角组合中的资产之一时才能按投资范围计算
+    pub fn applies(&self, pairs: [&TradingPair; 3]) -> bool {
+        self.max > 0.0 && pairs.iter().any(|p| p.base() == self.base || p.quote() == self.base)
+    }
+
+    // 候选投入量, investment_base 计
+    fn candidates(&self) -> Vec<f64> {
+        if self.step <= 0.0 || self.max <= self.min {
+            return vec![self.min.max(self.max)]
+        }
+        let n = (((self.max - self.min) / self.step + 1e-9).floor() as usize).min(MAX_CANDIDATES);
+        let mut spends: Vec<f64> = (0..=n).map(|i| self.min + i as f64 * self.step).collect();
+        if self.max - spends[n] > 1e-12 {
+            spends.push(self.max);
+        }
+        spends
+    }
+
+    // 在 rev 方向上搜索, eval 给出第一条腿计划成交 amount 个 base 时实际的 (成交数量, 投入, 收回), 以起始资产计
+    // 各资产之间按三角组合自身交易对的最优价格换算, 没有可成交的投入量时返回 None
+    pub fn size<F>(&self, tp: &TriPair, rev: bool, pairs: [&TradingPair; 3], tickers: [&Ticker; 3], eval: F) -> Option<Sized>
+    where
+        F: Fn(f64) -> Option<(f64, f64, f64)>,
+    {
+        let start = tp.start_asset(rev);
+        let to_start = rate(&self.base, &start, pairs, tickers)?;
+        let to_base = rate(&start, &self.base, pairs, tickers)?;
+
+        let mut best: Option<Sized> = None;
+        for spend in self.candidates() {
+            let amount = tp.start_qty(rev, spend * to_start, tickers)?;
+            let (amount, cost, end) = match eval(amount) {
+                Some(v) => v,
+                None => continue,
+            };
+            let sized = Sized {
+                spend: cost * to_base,
+                amount,
+                profit: (end - cost) * to_base,
+            };
+            if best.as_ref().is_none_or(|b| sized.profit > b.profit) {
+                best = Some(sized);
+            }
+        }
+        best
+    }
+}
+
+// 1 个 from 可以换得多少 to: 卖出用买一价, 买入用卖一价
+pub fn rate(from: &str, to: &str, pairs: [&TradingPair; 3], tickers: [&Ticker; 3]) -> Option<f64> {
+    if from == to {
+        return Some(1.0)
+    }
+    for (pair, tick) in pairs.iter().zip(tickers.iter()) {
+        if pair.base() == from && pair.quote() == to && tick.bb[0] > 0.0 {
+            return Some(tick.bb[0])
+        }
+        if pair.base() == to && pair.quote() == from && tick.ba[0] > 0.0 {
+            return Some(1.0 / tick.ba[0])
+        }
+    }
+    None
+}
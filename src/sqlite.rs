@@ -0,0 +1,102 @@
+use rusqlite::{params, Connection};
+
+use crate::error::Result;
+use crate::sink::OpportunitySink;
+use crate::tri_pair::Profit;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS opportunities (
+    id INTEGER PRIMARY KEY,
+    ts INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    rev INTEGER NOT NULL,
+    ratio REAL NOT NULL,
+    amount REAL NOT NULL,
+    profit REAL NOT NULL,
+    asset TEXT NOT NULL,
+    value REAL NOT NULL,
+    symbol1 TEXT NOT NULL, ask1 REAL NOT NULL, ask_qty1 REAL NOT NULL, bid1 REAL NOT NULL, bid_qty1 REAL NOT NULL,
+    symbol2 TEXT NOT NULL, ask2 REAL NOT NULL, ask_qty2 REAL NOT NULL, bid2 REAL NOT NULL, bid_qty2 REAL NOT NULL,
+    symbol3 TEXT NOT NULL, ask3 REAL NOT NULL, ask_qty3 REAL NOT NULL, bid3 REAL NOT NULL, bid_qty3 REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS opportunities_ts ON opportunities (ts);
+CREATE INDEX IF NOT EXISTS opportunities_name_ts ON opportunities (name, ts);
+";
+
+// record 在扫描线程上自动提交, WAL + synchronous=NORMAL 时提交不等待 fsync, 只在 checkpoint 时同步
+// 掉电最多丢失最近提交的几条机会, 数据库不会损坏
+const PRAGMAS: &str = "PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;";
+
+const INSERT: &str = "INSERT INTO opportunities (
+    ts, name, rev, ratio, amount, profit, asset, value,
+    symbol1, ask1, ask_qty1, bid1, bid_qty1,
+    symbol2, ask2, ask_qty2, bid2, bid_qty2,
+    symbol3, ask3, ask_qty3, bid3, bid_qty3
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+
+// 一个三角组合在一段时间内的机会统计
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriangleStats {
+    pub name: String,
+    pub count: u64,
+    pub avg_ratio: f64,
+    pub max_ratio: f64,
+    pub total_value: f64, // 换算为 report_currency 的收益之和
+}
+
+// 把机会写入本地 SQLite 数据库的 opportunities 表, 按时间和三角组合建索引
+#[derive(Debug)]
+pub struct SqliteSink {
+    conn: Connection,
+}
+
+impl SqliteSink {
+    // 打开或创建数据库, 表和索引不存在时创建
+    pub fn open(path: &str) -> Result<SqliteSink> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(PRAGMAS)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(SqliteSink { conn })
+    }
+
+    // 当前的日志模式, 文件数据库为 "wal"
+    pub fn journal_mode(&self) -> Result<String> {
+        Ok(self.conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?)
+    }
+
+    // 已写入的机会数量
+    pub fn count(&self) -> Result<u64> {
+        Ok(self.conn.query_row("SELECT COUNT(*) FROM opportunities", [], |row| row.get(0))?)
+    }
+
+    // since_ms 之后 (含) 每个三角组合的机会统计, 按次数从多到少
+    pub fn triangle_stats(&self, since_ms: i64) -> Result<Vec<TriangleStats>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT name, COUNT(*), AVG(ratio), MAX(ratio), SUM(value) FROM opportunities
+             WHERE ts >= ? GROUP BY name ORDER BY COUNT(*) DESC, name")?;
+        let rows = stmt.query_map([since_ms], |row| {
+            Ok(TriangleStats {
+                name: row.get(0)?,
+                count: row.get(1)?,
+                avg_ratio: row.get(2)?,
+                max_ratio: row.get(3)?,
+                total_value: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<TriangleStats>>>()?)
+    }
+}
+
+impl OpportunitySink for SqliteSink {
+    fn record(&mut self, profit: &Profit, symbols: [&str; 3]) -> Result<()> {
+        let [t1, t2, t3] = &profit.tickers;
+        let mut stmt = self.conn.prepare_cached(INSERT)?;
+        stmt.execute(params![
+            profit.ts as i64, profit.name, profit.rev, profit.ratio, profit.amount, profit.profit, profit.asset, profit.value,
+            symbols[0], t1.ba[0], t1.ba[1], t1.bb[0], t1.bb[1],
+            symbols[1], t2.ba[0], t2.ba[1], t2.bb[0], t2.bb[1],
+            symbols[2], t3.ba[0], t3.ba[1], t3.bb[0], t3.bb[1],
+        ])?;
+        Ok(())
+    }
+}
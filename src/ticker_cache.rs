@@ -1,13 +1,18 @@
 use std::collections::HashMap;
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::thread;
-use std::sync::atomic::AtomicBool;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use binance::websockets::*;
+use log::warn;
 
+use crate::market_data::{Backoff, MarketDataSource};
+use crate::metrics::Metrics;
+use crate::notifier::Notifier;
 use crate::tri_pair::{Ticker, TriPair};
 
+#[allow(dead_code)]
 #[derive(Debug)]
 pub struct TickerCache {
 	pairs: HashMap<u32, Vec<TriPair>>,
@@ -17,13 +22,11 @@ pub struct TickerCache {
 impl TickerCache {
 	pub fn new(pairs: HashMap<String, Vec<TriPair>>) -> TickerCache {
 		let mut symbol_id = HashMap::new();
-		let mut id: u32 = 1;
 		let mut npairs: HashMap<u32, Vec<TriPair>> = HashMap::new();
 		
-		for (symbol, _) in pairs.iter() {
+		for (id, (symbol, tps)) in (1u32..).zip(pairs.iter()) {
 			symbol_id.insert(symbol.clone(), id);
-			npairs.insert(id, pairs.get(symbol).unwrap().to_vec());
-			id = id +1;
+			npairs.insert(id, tps.to_vec());
 		}
 
 		TickerCache {
@@ -58,43 +61,103 @@ impl TickerCache {
 }
 
 
+// binance 全市场最优价格 (!bookTicker) 数据源
+#[derive(Debug, Default, Clone)]
+pub struct BinanceBookTicker {
+	bad: Arc<AtomicUsize>, // 无法解析而跳过的 ticker 数量
+	notifier: Option<Notifier>, // ws 断开时通知
+	metrics: Option<Metrics>, // 记录重连次数
+}
+
+impl BinanceBookTicker {
+	pub fn new() -> BinanceBookTicker {
+		BinanceBookTicker::default()
+	}
+
+	pub fn with_notifier(mut self, notifier: Option<Notifier>) -> BinanceBookTicker {
+		self.notifier = notifier;
+		self
+	}
+
+	pub fn with_metrics(mut self, metrics: Option<Metrics>) -> BinanceBookTicker {
+		self.metrics = metrics;
+		self
+	}
+
+	// 无法解析而跳过的 ticker 数量
+	pub fn bad_tickers(&self) -> usize {
+		self.bad.load(Ordering::Relaxed)
+	}
+}
+
+impl MarketDataSource for BinanceBookTicker {
+	fn subscribe(&mut self, symbol_id_map: HashMap<String, u32>) -> Receiver<Ticker> {
+		start_best_ticker(symbol_id_map, self.bad.clone(), self.notifier.clone(), self.metrics.clone())
+	}
+}
+
 // 订阅全市场最优价格, 一个线程
+// 连接失败或断开后按 Backoff 退避重连, 无法解析的 ticker 跳过并计入 bad
+// WebSockets 回调的返回类型 binance::errors::Result 由 binance crate 决定, 其 Err 无法缩小
+#[allow(clippy::result_large_err)]
 pub fn start_best_ticker(
 	symbol_id_map: HashMap<String, u32>,
+	bad: Arc<AtomicUsize>,
+	notifier: Option<Notifier>,
+	metrics: Option<Metrics>,
   ) -> Receiver<Ticker> {
 	let (in_tx, in_rx): (Sender<Ticker>, Receiver<Ticker>) = mpsc::channel();
 
-	// let thread_queue = queue.clone();
 	thread::spawn(move || {
+	  let mut backoff = Backoff::default();
 	  loop {
 		let keep_running = AtomicBool::new(true);
+		let received = AtomicBool::new(false);
 		let mut web_socket: WebSockets<'_> = WebSockets::new(|event: WebsocketEvent| {
 		  if let WebsocketEvent::BookTicker(depth_book_ticker) = event {
-				// println!("ws event: {}", depth_book_ticker.symbol);
-			  match symbol_id_map.get(depth_book_ticker.symbol.as_str()) {
-				None => (),
-				Some(id) => {
-				    // println!("ws event: {} {}", depth_book_ticker.symbol, id);
-					// tickers.write().unwrap().insert(*id, depth_book_ticker);
-					let ticker = Ticker::from(*id, &depth_book_ticker);
-					in_tx.send(ticker).unwrap();
-					()
-			  	}
+			if let Some(id) = symbol_id_map.get(depth_book_ticker.symbol.as_str()) {
+				received.store(true, Ordering::Relaxed);
+				match Ticker::from(*id, &depth_book_ticker) {
+					Ok(ticker) => {
+						// 扫描循环已退出, 停止接收
+						if in_tx.send(ticker).is_err() {
+							keep_running.store(false, Ordering::SeqCst);
+						}
+					}
+					Err(e) => {
+						let n = bad.fetch_add(1, Ordering::Relaxed) + 1;
+						warn!(symbol = depth_book_ticker.symbol.as_str(), error:% = e, skipped = n; "skip malformed ticker");
+					}
+				}
 			}
-			// .push_back(depth_book_ticker);
-			// println!("Adding to queue: {}", queue.read().unwrap().len());
 		  };
 		  Ok(())
 		});
 
-		web_socket.connect("!bookTicker").unwrap(); // check error
-		if web_socket.event_loop(&keep_running).is_err() {
-			println!("ws error occurs");
-		  	thread::sleep(Duration::from_secs(1));
+		let err = match web_socket.connect("!bookTicker") {
+			Ok(()) => web_socket.event_loop(&keep_running).err().map(|e| format!("ws error occurs: {}", e)),
+			Err(e) => Some(format!("ws connect failed: {}", e)),
+		};
+		let _ = web_socket.disconnect();
+		if !keep_running.load(Ordering::SeqCst) {
+			break;
+		}
+		if received.load(Ordering::Relaxed) {
+			backoff.reset();
+		}
+		let delay = backoff.next_delay();
+		if let Some(err) = &err {
+			if let Some(notifier) = &notifier {
+				notifier.notify(&format!("book ticker {}, reconnect in {:?}", err, delay));
+			}
+		}
+		if let Some(metrics) = &metrics {
+			metrics.ws_reconnected();
 		}
-		if web_socket.disconnect().is_err() {}
+		warn!(error = err.as_deref().unwrap_or("closed"), delay_ms = delay.as_millis() as u64; "book ticker ws disconnected, reconnecting");
+		thread::sleep(delay);
 	  }
 	});
   
 	in_rx
-  }
\ No newline at end of file
+  }
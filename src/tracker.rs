@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::tri_pair::Profit;
+
+// 一个套利机会从出现到消失的完整过程, 以三角组合名称和方向区分
+#[derive(Debug, Default, Clone)]
+pub struct Opportunity {
+    pub name: String,
+    pub rev: bool,
+    pub opened_ms: i64,    // 第一次超过阈值的时间
+    pub last_ms: i64,      // 最后一次超过阈值的时间
+    pub closed_ms: i64,    // 低于阈值或方向翻转的时间, 结束跟踪时为 last_ms
+    pub peak_ratio: f64,
+    pub peak_profit: f64,  // 以 asset 计
+    pub asset: String,
+    pub peak_value: f64,   // report_currency 计
+    pub ticks: u64,        // 持续期间超过阈值的 ticker 次数
+    pub executed: bool,    // 持续期间已交给执行器, 每个机会只执行一次
+    pub notified: bool,    // 持续期间已发送通知, 每个机会只通知一次
+}
+
+impl Opportunity {
+    pub fn duration_ms(&self) -> i64 {
+        self.closed_ms - self.opened_ms
+    }
+}
+
+impl fmt::Display for Opportunity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "opportunity {} rev={} lasted {}ms ({} ticks), peak ratio: {} profit: {} {} value: {}",
+            self.name, self.rev, self.duration_ms(), self.ticks, self.peak_ratio,
+            self.peak_profit, self.asset, self.peak_value)
+    }
+}
+
+// 一次 update 的结果
+#[derive(Debug, Default)]
+pub struct Update {
+    pub opened: bool,              // 本次出现新的机会
+    pub closed: Vec<Opportunity>,  // 本次结束的机会
+}
+
+// 机会跟踪: 超过阈值时开启, 持续期间只更新峰值, 低于阈值或方向翻转时结束
+// 每个机会只在结束时产生一个事件, 避免每个 ticker 重复输出
+#[derive(Debug, Default, Clone)]
+pub struct OpportunityTracker {
+    threshold: f64, // 比例, 配置中为百分比
+    open: HashMap<(String, bool), Opportunity>,
+}
+
+impl OpportunityTracker {
+    pub fn new(threshold: f64) -> OpportunityTracker {
+        OpportunityTracker {
+            threshold,
+            open: HashMap::new(),
+        }
+    }
+
+    // 三角组合 name 的最新收益, ts 为 ticker 的时间 (ms)
+    pub fn update(&mut self, name: &str, profit: &Profit, ts: i64) -> Update {
+        let mut update = Update::default();
+
+        // 未超过阈值: 结束该组合两个方向上正在持续的机会
+        if profit.ratio < self.threshold || profit.profit <= 0.0 {
+            for rev in [false, true].iter() {
+                if let Some(op) = self.close(name, *rev, ts) {
+                    update.closed.push(op);
+                }
+            }
+            return update
+        }
+
+        // 方向翻转, 结束另一个方向
+        if let Some(op) = self.close(name, !profit.rev, ts) {
+            update.closed.push(op);
+        }
+        let op = self.open.entry((name.to_string(), profit.rev)).or_insert_with(|| {
+            update.opened = true;
+            Opportunity {
+                name: name.to_string(),
+                rev: profit.rev,
+                opened_ms: ts,
+                asset: profit.asset.clone(),
+                ..Opportunity::default()
+            }
+        });
+        op.last_ms = ts;
+        op.ticks += 1;
+        if profit.ratio > op.peak_ratio || op.ticks == 1 {
+            op.peak_ratio = profit.ratio;
+            op.peak_profit = profit.profit;
+            op.asset = profit.asset.clone();
+            op.peak_value = profit.value;
+        }
+        update
+    }
+
+    fn close(&mut self, name: &str, rev: bool, ts: i64) -> Option<Opportunity> {
+        let mut op = self.open.remove(&(name.to_string(), rev))?;
+        op.closed_ms = ts;
+        Some(op)
+    }
+
+    pub fn is_open(&self, name: &str, rev: bool) -> bool {
+        self.open.contains_key(&(name.to_string(), rev))
+    }
+
+    // 正在持续的机会
+    pub fn get(&self, name: &str, rev: bool) -> Option<&Opportunity> {
+        self.open.get(&(name.to_string(), rev))
+    }
+
+    // 标记正在持续的机会已执行, 机会结束前不再执行
+    pub fn mark_executed(&mut self, name: &str, rev: bool) {
+        if let Some(op) = self.open.get_mut(&(name.to_string(), rev)) {
+            op.executed = true;
+        }
+    }
+
+    // 标记正在持续的机会已通知, 机会结束前不再通知
+    pub fn mark_notified(&mut self, name: &str, rev: bool) {
+        if let Some(op) = self.open.get_mut(&(name.to_string(), rev)) {
+            op.notified = true;
+        }
+    }
+
+    // 正在持续的机会数量
+    pub fn open_count(&self) -> usize {
+        self.open.len()
+    }
+
+    // 结束跟踪, 仍在持续的机会以最后一次超过阈值的时间结束, 按开启时间排序
+    pub fn finish(&mut self) -> Vec<Opportunity> {
+        let mut ops: Vec<Opportunity> = self.open.drain().map(|(_, mut op)| {
+            op.closed_ms = op.last_ms;
+            op
+        }).collect();
+        ops.sort_by(|a, b| a.opened_ms.cmp(&b.opened_ms).then_with(|| a.name.cmp(&b.name)));
+        ops
+    }
+}
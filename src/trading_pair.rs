@@ -1,7 +1,47 @@
 use std::fmt;
 use std::time::SystemTime;
 
-use crate::tri_pair::Ticker;
+// 交易所过滤器 (LOT_SIZE, MIN_NOTIONAL), 为 0 表示不限制; 只下市价单, 不需要 PRICE_FILTER
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PairFilters {
+    pub step: f64,
+    pub min_qty: f64,
+    pub max_qty: f64,
+    pub min_notional: f64,
+}
+
+impl PairFilters {
+    // 按 step 取整后的成交数量, 不超过盘口数量和 max_qty; 低于 min_qty 或 min_notional 时无法成交
+    pub fn fill_qty(&self, want: f64, book_qty: f64, price: f64) -> Option<f64> {
+        let qty = self.round_qty(want.min(book_qty));
+        if !self.accepts(qty, qty * price) {
+            return None
+        }
+        Some(qty)
+    }
+
+    // 不超过 max_qty, 按 step 向下取整
+    pub fn round_qty(&self, qty: f64) -> f64 {
+        let qty = if self.max_qty > 0.0 { qty.min(self.max_qty) } else { qty };
+        round_step(qty, self.step)
+    }
+
+    // 成交 qty 个 base, 成交额为 notional 时是否满足 min_qty 和 min_notional
+    pub fn accepts(&self, qty: f64, notional: f64) -> bool {
+        qty > 0.0 && qty >= self.min_qty && notional >= self.min_notional
+    }
+}
+
+// 把数量向下取整到 step 的整数倍
+pub fn round_step(qty: f64, step: f64) -> f64 {
+    if step > 0.0 {
+        // 避免浮点误差把 3.0 / 0.1 算成 29.999..
+        ((qty / step) + 1e-9).floor() * step
+    } else {
+        qty
+    }
+}
+
 /*
  *  TradingPair
  */
@@ -13,9 +53,11 @@ pub struct TradingPair {
     base_asset: String,
     quote_asset: String,
     pub step: f64,
-    pub tick: Ticker,
+    pub filters: PairFilters,
+    // 最新 ticker 保存在 TriAngleArb 中按 idx 索引的 Vec 里
     // pub bids: Bids,
     pub fee: f64,
+    #[allow(dead_code)]
     timestamp: SystemTime,
 }
 
@@ -42,7 +84,7 @@ impl TradingPair {
 			base_asset,
 			quote_asset,
 			step,
-            tick: Ticker::default(),
+            filters: PairFilters { step, ..PairFilters::default() },
 			// asks: Asks{ price: 0.0, qty: 0.0, },
 			// bids: Bids{ price: 0.0, qty: 0.0, },
             fee,
@@ -50,6 +92,13 @@ impl TradingPair {
 		}
     }
 
+    // 设置交易所过滤器, step 以 filters.step 为准
+    pub fn with_filters(mut self, filters: PairFilters) -> TradingPair {
+        self.step = filters.step;
+        self.filters = filters;
+        self
+    }
+
     // // Setters
     // pub fn update(&mut self, timestamp: SystemTime, asks: Asks, bids: Bids) {
     //     self.asks = asks;
@@ -67,6 +116,11 @@ impl TradingPair {
       	self.symbol.to_string()
     }
 
+    // 交易所的交易对名称, 不拷贝
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
     pub fn get_symbol_id(&self) -> String {
         self.symbol_id.to_string()
     }
@@ -3,12 +3,12 @@ use std::fmt;
 // use chrono::TimeZone;
 use string_join::Join;
 // use time;
-use chrono::prelude::Local;
+use chrono::prelude::{Local, Utc};
 
 use binance::model::BookTickerEvent;
-use crate::trading_pair::TradingPair;
-
-pub const TX_FEE: f64 = 0.999;
+use log::{debug, info, log, log_enabled, Level};
+use crate::error::{parse_f64, Result};
+use crate::trading_pair::{PairFilters, TradingPair};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Side {
@@ -16,34 +16,77 @@ pub enum Side {
     SideSell,
 }
 
+impl Side {
+    // 反方向
+    pub fn opposite(&self) -> Side {
+        if *self == Side::SideBuy { Side::SideSell } else { Side::SideBuy }
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Ticker {
     pub idx: u32,
     pub ba: [f64; 2], // best ask; 0 is price, 1 is qty
     pub bb: [f64; 2], // best bid
+    pub update_id: u64, // 交易所的更新 id, 0 表示未知
+    pub ts: i64, // 本地接收时间 (毫秒), 0 表示未知, 不检查是否过期
 }
 
 impl Ticker {
-    pub fn from(idx: u32, depth: &BookTickerEvent) -> Self {
-        Ticker {
+    // 价格或数量无法解析时返回错误, 由数据源跳过该消息
+    pub fn from(idx: u32, depth: &BookTickerEvent) -> Result<Self> {
+        Ok(Ticker {
             idx,
-            ba: [depth.best_ask.parse().unwrap(), depth.best_ask_qty.parse().unwrap()],
-            bb: [depth.best_bid.parse().unwrap(), depth.best_bid_qty.parse().unwrap()],
-        }
+            ba: [parse_f64("best ask", &depth.best_ask)?, parse_f64("best ask qty", &depth.best_ask_qty)?],
+            bb: [parse_f64("best bid", &depth.best_bid)?, parse_f64("best bid qty", &depth.best_bid_qty)?],
+            update_id: depth.update_id,
+            ts: Utc::now().timestamp_millis(),
+        })
     }
 }
 
 // 计算三角套利的盈利
 #[derive(Debug, Default, Clone)]
 pub struct Profit {
+    pub name: String, // 三角套利组合的名称, TriPair::name
     pub rev: bool,  // true: 从第二个发起; false: p1-p2-p3
     pub ratio: f64,
     pub amount: f64,
-    pub profit: f64,
+    pub profit: f64, // 以 asset 计
+    pub asset: String, // profit 的计价资产: 起始资产, 按投资范围计算时为 investment_base
+    pub value: f64, // profit 按最新 ticker 换算为 report_currency 的数量, 无法换算时为 0
+    pub invest: f64, // 按投资范围选择的投入, investment_base 计; 0 表示未按投资范围计算
     pub ts: u64, // timestamp
     pub tickers: [Ticker; 3]
 }
 
+impl Profit {
+    // value 是否可用: 有收益但无法换算为 report_currency 时 value 为 0, 不可用
+    pub fn has_value(&self) -> bool {
+        self.value != 0.0 || self.profit == 0.0
+    }
+
+    // 两者的 value 都可用时按 value 比较, 否则按 ratio 比较, 无法换算的机会不会被当作没有收益
+    pub fn better_than(&self, other: &Profit) -> bool {
+        if self.has_value() && other.has_value() {
+            self.value > other.value
+        } else {
+            self.ratio > other.ratio
+        }
+    }
+}
+
+// 一条腿的成交, 模拟时按盘口价格, 实盘时按成交回报
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub idx: u32,
+    pub side: Side,
+    pub price: f64,
+    pub qty: f64,      // base 数量, 已按 step 取整
+    pub spent: f64,    // 付出的资产数量, 买: quote, 卖: base
+    pub received: f64, // 扣除手续费后得到的资产数量, 买: base, 卖: quote
+}
+
 #[derive(Debug, Clone)]
 pub struct TriPair {
     pub coin: String,
@@ -51,6 +94,10 @@ pub struct TriPair {
     pub dirs: [Side; 3],
     pub pairs: [u32; 3],
     pub pairs_name: [String; 3],
+    pub quotes: [String; 2], // 正向和反向的起始资产, 非循环三角即前两个交易对的 quote
+    pub fees: [f64; 3], // 每个交易对的手续费乘数, TradingPair::fee
+    pub filters: [PairFilters; 3], // 每个交易对的交易所过滤器
+    #[allow(dead_code)]
     profit: Profit,
 }
 
@@ -64,24 +111,22 @@ impl TriPair {
     pub fn new(coin: String, pairs: Vec<&TradingPair>) -> TriPair {
         let n_pairs: [u32; 3] = [pairs[0].get_symbol_idx(), pairs[1].get_symbol_idx(), pairs[2].get_symbol_idx()]; //pairs.clone();
         let pairs_name: [String; 3] = [pairs[0].text(), pairs[1].text(), pairs[2].text()];
-        let dirs: [Side; 3];
-        let name: String;
-
-        if pairs[0].quote() == pairs[2].quote() {
-            // ada/usdt ada/btc btc/usdt
-            dirs = [Side::SideBuy, Side::SideSell, Side::SideSell];
-            // let ss = [pairs[0].base(), pairs[0].quote(), pairs[2].base()];
-            // ss.sort();
-            name = "-".join([pairs[0].base(), pairs[0].quote(), pairs[2].base()]);
-        } else if pairs[0].quote() == pairs[2].base() {
-            // ada/btc ada/usdt btc/usdt
-            dirs = [Side::SideBuy, Side::SideSell, Side::SideBuy];
-            // let ss = vec!;
-            // ss.sort();
-            name = "-".join([pairs[0].base(), pairs[0].quote(), pairs[2].quote()]);
-        } else {
+        let fees: [f64; 3] = [pairs[0].fee, pairs[1].fee, pairs[2].fee];
+        let filters: [PairFilters; 3] = [pairs[0].filters.clone(), pairs[1].filters.clone(), pairs[2].filters.clone()];
+        // 相邻两个交易对共有的资产: x 为第一条腿换得的资产, y 为第二条腿换得的资产, z 为起始资产
+        // ada/btc ada/usdt btc/usdt: x=ada y=usdt z=btc; 循环三角 a/b c/a b/c: x=a y=c z=b
+        let shared = |a: &TradingPair, b: &TradingPair| if b.has_asset(a.base()) { a.base() } else { a.quote() };
+        let x = shared(pairs[0], pairs[1]);
+        let y = shared(pairs[1], pairs[2]);
+        let z = shared(pairs[2], pairs[0]);
+        if x == y || y == z || z == x || !pairs[1].has_asset(x.clone()) || !pairs[2].has_asset(y.clone()) || !pairs[0].has_asset(z.clone()) {
             panic!("invalid pair: {} {} {}", pairs[0].get_symbol(), pairs[1].get_symbol(), pairs[2].get_symbol());
         }
+        // 每条腿换得的资产是 base 时买入, 否则卖出
+        let side = |pair: &TradingPair, get: &String| if pair.base() == *get { Side::SideBuy } else { Side::SideSell };
+        let dirs: [Side; 3] = [side(pairs[0], &x), side(pairs[1], &y), side(pairs[2], &z)];
+        let quotes: [String; 2] = [z, y.clone()];
+        let name: String = "-".join([pairs[0].base(), pairs[0].quote(), y]);
 
         TriPair {
             coin,
@@ -89,110 +134,148 @@ impl TriPair {
             dirs,
             pairs: n_pairs,
             pairs_name,
+            quotes,
+            fees,
+            filters,
             profit: Profit::default(),
         }
     }
 
-    // 计算三角套利组合的盈利
-    pub fn calc_profit(&self, t0: &Ticker, t1: &Ticker, t2: &Ticker, print: bool) -> Profit {
-        // let p0 = self.pairs[0];
-        // let p1 = self.pairs[1];
-        // let p2 = self.pairs[2];
-        if t0.ba[0] == 0.0 { // || t0.ba[1] == 0.0 {
-            // println!("angle {} {} ask ticker 0 is zero", self.name, self.pairs_name[0]);
-            return Profit::default();
-        }
-        // if t0.bb[0] == 0.0 || t0.bb[1] == 0.0 {
-        //     println!("angle {} {} bid ticker 0 is zero", self.name, self.pairs_name[0]);
-        //     return Profit::default();
-        // }
-        if t1.ba[0] == 0.0 { // || t1.ba[1] == 0.0 {
-            // println!("angle {} {} ask ticker 0 is zero", self.name, self.pairs_name[1]);
-            return Profit::default();
-        }
-        // if t1.bb[0] == 0.0 || t1.bb[1] == 0.0 {
-        //     println!("angle {} {} bid ticker 0 is zero", self.name, self.pairs_name[1]);
-        //     return Profit::default();
-        // }
-        if t2.ba[0] == 0.0 { //|| t2.ba[1] == 0.0 {
-            // println!("angle {} {} ask ticker 0 is zero", self.name, self.pairs_name[2]);
-            return Profit::default();
-        }
-        // if t2.bb[0] == 0.0 || t2.bb[1] == 0.0 {
-        //     println!("angle {} {} bid ticker 0 is zero", self.name, self.pairs_name[2]);
-        //     return Profit::default();
-        // }
-
-        let mut vol = t0.ba[1]; // p0.asks.qty;
-        if vol > t1.bb[1] {
-            vol = t1.bb[1];
-        }
-        // token/btc token/usdt btc/usdt
-        // 1. 买 token 需要的 btc
-        let btc = vol * t0.ba[0] / TX_FEE;
-        // 2. 卖出 token 得到的 usdt
-        let usdt = vol * t1.bb[0] * TX_FEE;
-        let end_btc;
-        if self.dirs[2] == Side::SideSell {
-            end_btc = usdt * t2.bb[0] * TX_FEE;
+    // 三条腿的执行顺序: (交易对 id, 方向)
+    // rev 为 false 时 p1-p2-p3, 为 true 时从第二个交易对发起 p2-p1-p3, 每条腿方向相反
+    pub fn legs(&self, rev: bool) -> [(u32, Side); 3] {
+        if rev {
+            [(self.pairs[1], self.dirs[1].opposite()), (self.pairs[0], self.dirs[0].opposite()), (self.pairs[2], self.dirs[2].opposite())]
         } else {
-            end_btc = usdt * TX_FEE / t2.ba[0];
+            [(self.pairs[0], self.dirs[0].clone()), (self.pairs[1], self.dirs[1].clone()), (self.pairs[2], self.dirs[2].clone())]
         }
+    }
 
-        // 第二种情况
-        let mut vol2: f64 = t1.ba[1];
-        if vol2 > t0.bb[1] {
-            vol2 = t0.bb[1];
+    // 循环三角 a/b c/a b/c: 每个资产只作为一次 base, 三条腿方向相同
+    pub fn is_cyclic(&self) -> bool {
+        self.dirs[0] == self.dirs[1]
+    }
+
+    // rev 方向的起始资产, 即第一条腿付出的资产: 买入时为 quote, 卖出时为 base
+    pub fn start_asset(&self, rev: bool) -> String {
+        self.quotes[rev as usize].clone()
+    }
+
+    // rev 方向投入 spend 个起始资产时第一条腿成交的 base 数量, 买入按卖一价换算; tickers 与 pairs 顺序一致
+    pub fn start_qty(&self, rev: bool, spend: f64, tickers: [&Ticker; 3]) -> Option<f64> {
+        let k = rev as usize;
+        if self.legs(rev)[0].1 == Side::SideSell {
+            return Some(spend)
         }
-        // 1. 通过 b2/q2 买入, 需要的 usdt 数量
-        let usdt2 = vol2 * t1.ba[0] / TX_FEE;
-        // 2. 通过 b1/q1 卖出, 得到的 btc 数量
-        let btc2 = vol2 * t0.bb[0] * TX_FEE;
-        let end_usdt: f64;
-        let profit2: f64;
-        let profit2_btc: f64;
-        if self.dirs[2] == Side::SideSell {
-            // token/q1 token/q2 q2/q1 buy usdt/btc
-            end_usdt = btc2 * TX_FEE / t2.ba[0];
-            profit2 = end_usdt - usdt2;
-            profit2_btc = profit2 * t2.bb[0];
-        } else {
-            // q1/q2 sell btc/usdt
-            end_usdt = btc2 * t2.bb[0] * TX_FEE;
-            profit2 = end_usdt - usdt2;
-            profit2_btc = profit2 / t2.ba[0];
+        let price = tickers[k].ba[0];
+        if price <= 0.0 {
+            return None
         }
+        Some(spend / price)
+    }
 
-        let mut profit = end_btc - btc;
-        let ratio: f64;
-        let rev: bool;
-        if profit < profit2_btc {
-            profit = profit2;
-            vol = vol2;
-            ratio = profit / usdt2;
-            rev = true;
-        } else {
-            ratio = profit / btc;
-            rev = false;
-        }
-        if profit > 0.0 && print {
-            println!("calc profit: t1: [{} {}]  [{} {}]", t0.ba[0], t0.ba[1], t0.bb[0], t0.bb[1]);
-            println!("calc profit: t2: [{} {}]  [{} {}]", t1.ba[0], t1.ba[1], t1.bb[0], t1.bb[1]);
-            println!("calc profit: t3: [{} {}]  [{} {}]", t2.ba[0], t2.ba[1], t2.bb[0], t2.bb[1]);
-            if self.dirs[2] == Side::SideSell {
-                println!("calc profit1: b1: {} q1: {} b2: {} q2: {} b3: {} q3: {}", vol, btc, vol, usdt, end_btc, usdt);
-                println!("calc profit2: b1: {} q1: {} b2: {} q2: {} b3: {} q3: {}", vol2, btc2, vol2, usdt2, usdt2, end_usdt);
-            } else {
-                println!("calc profit1: b1: {} q1: {} b2: {} q2: {} b3: {} q3: {}", vol, btc, vol, usdt, usdt, end_btc);
-                println!("calc profit2: b1: {} q1: {} b2: {} q2: {} b3: {} q3: {}", vol2, btc2, vol2, usdt2, usdt2, end_usdt);
+    // rev 方向前两条腿最优一档可以成交的第一条腿 base 数量, 不计手续费
+    fn top_qty(&self, rev: bool, tickers: [&Ticker; 3]) -> f64 {
+        let legs = self.legs(rev);
+        let (t0, t1) = if rev { (tickers[1], tickers[0]) } else { (tickers[0], tickers[1]) };
+        let first = if legs[0].1 == Side::SideBuy { t0.ba[1] } else { t0.bb[1] };
+        // 第二条腿能接收的第一条腿换得的资产数量, 卖出时为 base 数量, 买入时为 quote 金额
+        let second = if legs[1].1 == Side::SideSell { t1.bb[1] } else { t1.ba[0] * t1.ba[1] };
+        let second = if legs[0].1 == Side::SideBuy { second } else { second / t0.bb[0] };
+        first.min(second)
+    }
+
+    // 以 tickers 快照 (与 pairs 顺序一致), 按 rev 指定的顺序成交三条腿, 第一条腿买入或卖出 amount 个 base
+    // 每条腿都按盘口价格成交, 按交易对的 filters 取整和校验, 中间资产的零头不计入收益
+    // 手续费使用 fees, 任意一条腿无法成交时返回 None
+    pub fn fill_legs(&self, rev: bool, amount: f64, tickers: [&Ticker; 3]) -> Option<Vec<Fill>> {
+        let mut fills: Vec<Fill> = Vec::with_capacity(3);
+        self.walk_legs(rev, amount, tickers, |fill| fills.push(fill))?;
+        Some(fills)
+    }
+
+    // 与 fill_legs 相同, 只返回 (第一条腿成交数量, 投入, 收回), 不分配内存, 用于逐个 ticker 的计算
+    pub fn exec_legs(&self, rev: bool, amount: f64, tickers: [&Ticker; 3]) -> Option<(f64, f64, f64)> {
+        let (mut qty, mut spent, mut received) = (0.0, 0.0, 0.0);
+        let mut n = 0;
+        self.walk_legs(rev, amount, tickers, |fill| {
+            if n == 0 {
+                qty = fill.qty;
+                spent = fill.spent;
             }
+            received = fill.received;
+            n += 1;
+        })?;
+        Some((qty, spent, received))
+    }
+
+    fn walk_legs<F: FnMut(Fill)>(&self, rev: bool, amount: f64, tickers: [&Ticker; 3], mut on_fill: F) -> Option<()> {
+        let mut holding = 0.0;
+
+        for (n, (idx, side)) in self.legs(rev).iter().enumerate() {
+            let k = self.pairs.iter().position(|p| p == idx)?;
+            let tick = tickers[k];
+            let filters = &self.filters[k];
+            let fee = self.fees[k];
+            let fill = if *side == Side::SideBuy {
+                let price = tick.ba[0];
+                if price <= 0.0 {
+                    return None
+                }
+                let want = if n == 0 { amount } else { holding / price };
+                let qty = filters.fill_qty(want, tick.ba[1], price)?;
+                Fill { idx: *idx, side: side.clone(), price, qty, spent: qty * price, received: qty * fee }
+            } else {
+                let price = tick.bb[0];
+                let want = if n == 0 { amount } else { holding };
+                let qty = filters.fill_qty(want, tick.bb[1], price)?;
+                Fill { idx: *idx, side: side.clone(), price, qty, spent: qty, received: qty * price * fee }
+            };
+            holding = fill.received;
+            on_fill(fill);
+        }
+
+        Some(())
+    }
+
+    // 计算三角套利组合的盈利, 有收益时在 debug 级别输出计算过程 (print 为 true 时为 info 级别)
+    // 两个方向各以前两条腿最优一档可以成交的数量, 按交易所过滤器取整后成交三条腿, 取收益较大的方向
+    pub fn calc_profit(&self, t0: &Ticker, t1: &Ticker, t2: &Ticker, print: bool) -> Profit {
+        if t0.ba[0] == 0.0 || t1.ba[0] == 0.0 || t2.ba[0] == 0.0 {
+            return Profit::default();
+        }
+
+        // 两个方向都无法成交时没有机会 (保留 name, 用于结束已有的机会)
+        let tickers = [t0, t1, t2];
+        let exec1 = self.exec_legs(false, self.top_qty(false, tickers), tickers);
+        let exec2 = self.exec_legs(true, self.top_qty(true, tickers), tickers);
+        // 反向的收益以 q2 计, 换算为 q1 比较 (第三条腿正向把 q2 换为 q1)
+        let to_q1 = |v: f64| if self.dirs[2] == Side::SideSell { v * t2.bb[0] } else { v / t2.ba[0] };
+        let (rev, (amount, start, end)) = match (exec1, exec2) {
+            (None, None) => return Profit { name: self.name.clone(), ..Profit::default() },
+            (Some(e1), None) => (false, e1),
+            (None, Some(e2)) => (true, e2),
+            (Some(e1), Some(e2)) => if e1.2 - e1.1 < to_q1(e2.2 - e2.1) { (true, e2) } else { (false, e1) },
+        };
+        let profit = end - start;
+        let ratio = profit / start;
+        let level = if print { Level::Info } else { Level::Debug };
+        if profit > 0.0 && log_enabled!(level) {
+            log!(level, "calc profit {}: t1: [{} {}]  [{} {}]", self.name, t0.ba[0], t0.ba[1], t0.bb[0], t0.bb[1]);
+            log!(level, "calc profit {}: t2: [{} {}]  [{} {}]", self.name, t1.ba[0], t1.ba[1], t1.bb[0], t1.bb[1]);
+            log!(level, "calc profit {}: t3: [{} {}]  [{} {}]", self.name, t2.ba[0], t2.ba[1], t2.bb[0], t2.bb[1]);
+            log!(level, "calc profit {}: rev={} amount={} {} -> {}", self.name, rev, amount, start, end);
         }
 
         Profit {
+            name: self.name.clone(),
             rev,
             ratio,
-            amount: vol,
+            amount,
             profit,
+            asset: self.start_asset(rev),
+            value: 0.0,
+            invest: 0.0,
             ts:  Local::now().timestamp_millis() as u64, // time::OffsetDateTime::now_utc().unix_timestamp_nanos() as u64, // timestamp
             tickers: [
                 t0.clone(),
@@ -205,23 +288,20 @@ impl TriPair {
 
 // 三角套利的 桥 交易对, 例如 BTC/USDT ETH/USDT ETH/BTC
 fn get_bridge_pairs<'a>(
-        pairs: &'a Vec<TradingPair>,
-        base_quotes: &'a Vec<String>
+        pairs: &'a [TradingPair],
+        base_quotes: &'a [String]
     ) -> HashMap<String, &'a TradingPair> {
     let mut bp: HashMap<String, &TradingPair> = HashMap::new();
-    let l = base_quotes.len();
     let mpairs: HashMap<String, &TradingPair> = pairs.iter().map(|x| (x.text(), x)).collect();
 
-    for i in 0..l {
-        let t1 = base_quotes[i].clone();
-        for j in i+1..l {
-            let t2 = base_quotes[j].clone();
-            let s1 = t1.clone() + "/" + &t2;
-            let s2 = t2 + "/" + &t1;
-            if mpairs.get(&s1).is_some() {
-                bp.insert(s1.clone(), mpairs.get(&s1).unwrap());
-            } else if mpairs.get(&s2).is_some() {
-                bp.insert(s2.clone(), mpairs.get(&s2).unwrap());
+    for (i, t1) in base_quotes.iter().enumerate() {
+        for t2 in &base_quotes[i+1..] {
+            let s1 = t1.clone() + "/" + t2;
+            let s2 = t2.clone() + "/" + t1;
+            if let Some(pair) = mpairs.get(&s1) {
+                bp.insert(s1, pair);
+            } else if let Some(pair) = mpairs.get(&s2) {
+                bp.insert(s2, pair);
             }
         }
     }
@@ -233,8 +313,8 @@ fn get_bridge_pairs<'a>(
 // 注意: 所有三角套利组合的 pair 都是引用类型, 即所有三角套利组合共享交易对结构体 TradingPair, 因此, 
 // 可以让所有的三角套利组合共享交易对的最新 ticker 数据
 pub fn derive_tri_pairs(
-        pairs: &Vec<TradingPair>,
-        base_quotes: &Vec<String>,
+        pairs: &[TradingPair],
+        base_quotes: &[String],
         allow_coins: Option<Vec<String>>,
         exclude_coins: Option<Vec<String>>
     ) -> HashMap<String, Vec<TriPair>> {
@@ -246,7 +326,28 @@ pub fn derive_tri_pairs(
     //         // base_quotes.iter().find(|&ele| ele.eq(&x.quote()) ).is_some()
     //     })
     //     .collect();
-    let bridges: HashMap<String, &TradingPair> = get_bridge_pairs(pairs, &base_quotes);
+    let bridges: HashMap<String, &TradingPair> = get_bridge_pairs(pairs, base_quotes);
+    info!(count = bridges.len(); "bridges: {}", bridges.keys().fold("".to_string(), |mut acc, p| { acc.push_str(p); acc.push(' '); acc }));
+    derive_with_bridges(pairs, &bridges, allow_coins, exclude_coins)
+}
+
+// 在全部交易对上推导三角套利交易对: 任意交易对都可以作为桥, 即枚举所有 coin/X coin/Y X/Y 形式的闭合三角
+// 不再局限于 base_quotes 之间的桥, 可以得到 ETH/BNB ADA/ETH ADA/BNB 这类以冷门 quote 组成的三角
+pub fn derive_all_tri_pairs(
+        pairs: &[TradingPair],
+        allow_coins: Option<Vec<String>>,
+        exclude_coins: Option<Vec<String>>
+    ) -> HashMap<String, Vec<TriPair>> {
+    let bridges: HashMap<String, &TradingPair> = pairs.iter().map(|x| (x.text(), x)).collect();
+    derive_with_bridges(pairs, &bridges, allow_coins, exclude_coins)
+}
+
+fn derive_with_bridges(
+        pairs: &[TradingPair],
+        bridges: &HashMap<String, &TradingPair>,
+        allow_coins: Option<Vec<String>>,
+        exclude_coins: Option<Vec<String>>
+    ) -> HashMap<String, Vec<TriPair>> {
     let mut coin_map: HashMap<String, Vec<&TradingPair>> = HashMap::new();
 
     // 以 base 为key, 把 base 相同的交易对提取出来, 组成一个数组, 作为 value 放到 coin_map
@@ -258,26 +359,35 @@ pub fn derive_tri_pairs(
         }
     }
 
-    println!("bridges: {}", bridges.keys().fold("".to_string(), |mut acc, p| { acc.push_str(p); acc.push_str(" "); acc }));
     // TOKENA -> [[TOKENA/BTC, TOKENA/USDT, BTC/USDT], [TOKENA/ETH, TOKENA/BTC, ETH/BTC]]
     let mut tri_pairs_map: HashMap<String, Vec<TriPair>> = HashMap::new();
     for (coin, coin_pairs) in coin_map {
-        if (allow_coins.is_some()) && (vec_has_coin(&allow_coins, &coin) == false) {
+        if (allow_coins.is_some()) && !vec_has_coin(&allow_coins, &coin) {
             continue
         }
-        if (exclude_coins.is_some()) && (vec_has_coin(&exclude_coins, &coin) == true) {
+        if (exclude_coins.is_some()) && vec_has_coin(&exclude_coins, &coin) {
             continue
         }
-        if let Some(tri_pair) = find_coin_tri_pairs(coin.as_str(), &coin_pairs, &bridges) {
+        if let Some(tri_pair) = find_coin_tri_pairs(coin.as_str(), &coin_pairs, bridges) {
             tri_pairs_map.insert(coin.clone(), tri_pair);
         }
     }
 
-    for (coin, ps) in &tri_pairs_map {
-        // let mut pn = "".to_string();
-        // ps.iter().map(|x| { pn.push_str(" "); pn.push_str(&x.name); x.coin}).collect();
-        let pn = ps.iter().fold("".to_string(), |mut acc, x| { acc.push_str(" "); acc.push_str(&x.name); acc});
-        println!("coin: {} pairs: [{}]", coin, pn);
+    // 循环三角 A/B C/A B/C 中每个资产只作为一次 base, 上面按 coin 推导不到
+    let scanned = |coin: &String| {
+        (allow_coins.is_none() || vec_has_coin(&allow_coins, coin)) && !vec_has_coin(&exclude_coins, coin)
+    };
+    for tp in find_cyclic_tri_pairs(pairs, bridges, scanned) {
+        tri_pairs_map.entry(tp.coin.clone()).or_default().push(tp);
+    }
+
+    if log_enabled!(Level::Debug) {
+        for (coin, ps) in &tri_pairs_map {
+            // let mut pn = "".to_string();
+            // ps.iter().map(|x| { pn.push_str(" "); pn.push_str(&x.name); x.coin}).collect();
+            let pn = ps.iter().fold("".to_string(), |mut acc, x| { acc.push(' '); acc.push_str(&x.name); acc});
+            debug!("coin: {} pairs: [{}]", coin, pn);
+        }
     }
 
     tri_pairs_map
@@ -293,32 +403,20 @@ pub fn to_tri_angle_symbol(
     // let bs_map: HashMap<String, bool> = base_quotes.iter().map(|x| (x.clone(), true)).collect();
 
     // 提取 交易对 关联的三角套利组, 当该交易对的 ticker 变化时, 触发重新计算所有三角套利组的收益
-    for (_, pairs) in tri_pairs_map {
+    for pairs in tri_pairs_map.values() {
         for tp in pairs {
             let p0 = tp.pairs[0];
             let p1 = tp.pairs[1];
             let p2 = tp.pairs[2];
 
             // if !is_base_symbol(id_pair.get(&p0).unwrap(), &bs_map) {
-                if tri_pairs.contains_key(&p0) {
-                    tri_pairs.get_mut(&p0).unwrap().push(tp.clone());
-                } else {
-                    tri_pairs.insert(p0, vec![tp.clone()]);
-                }
+                tri_pairs.entry(p0).or_default().push(tp.clone());
             // }
             // if !is_base_symbol(id_pair.get(&p1).unwrap(), &bs_map) {
-                if tri_pairs.contains_key(&p1) {
-                    tri_pairs.get_mut(&p1).unwrap().push(tp.clone());
-                } else {
-                    tri_pairs.insert(p1, vec![tp.clone()]);
-                }
+                tri_pairs.entry(p1).or_default().push(tp.clone());
             // }
             // if !is_base_symbol(id_pair.get(&p2).unwrap(), &bs_map) {
-                if tri_pairs.contains_key(&p2) {
-                    tri_pairs.get_mut(&p2).unwrap().push(tp.clone());
-                } else {
-                    tri_pairs.insert(p2, vec![tp.clone()]);
-                }
+                tri_pairs.entry(p2).or_default().push(tp.clone());
             // }
         }
     }
@@ -326,6 +424,27 @@ pub fn to_tri_angle_symbol(
     tri_pairs
 }
 
+// 三角套利组合的下标索引: 每个组合在返回的数组中只保存一份, 以交易对 id 为下标得到关联组合的下标
+// size 为交易对 id 的上界, 组合按名称排序
+pub fn to_tri_angle_index(
+        tri_pairs_map: &HashMap<String, Vec<TriPair>>,
+        size: usize,
+    ) -> (Vec<TriPair>, Vec<Vec<usize>>) {
+    let mut triangles: Vec<TriPair> = tri_pairs_map.values().flatten().cloned().collect();
+    triangles.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut index: Vec<Vec<usize>> = vec![Vec::new(); size];
+
+    for (id, tp) in triangles.iter().enumerate() {
+        for p in tp.pairs.iter() {
+            if let Some(ids) = index.get_mut(*p as usize) {
+                ids.push(id);
+            }
+        }
+    }
+
+    (triangles, index)
+}
+
 // fn is_base_symbol(
 //         tp: &TradingPair,
 //         bs_map: &HashMap<String, bool>
@@ -336,8 +455,47 @@ pub fn to_tri_angle_symbol(
 fn vec_has_coin(coins: &Option<Vec<String>>, coin: &String) -> bool {
     match coins {
         None => false,
-        Some(coins) => coins.contains(&coin)
+        Some(coins) => coins.contains(coin)
+    }
+}
+
+// 循环三角: coin/X 与 Y/coin 通过桥交易对 X/Y 闭合, 即交易对图上每个资产只作为一次 base 的三角
+// 同一个三角可以从三个资产出发得到, 只保留 coin 通过 scanned 且名称最小的一个
+fn find_cyclic_tri_pairs<F: Fn(&String) -> bool>(
+        pairs: &[TradingPair],
+        bridges: &HashMap<String, &TradingPair>,
+        scanned: F
+    ) -> Vec<TriPair> {
+    let mut by_quote: HashMap<String, Vec<&TradingPair>> = HashMap::new();
+    for pair in pairs.iter() {
+        by_quote.entry(pair.quote()).or_default().push(pair);
+    }
+
+    let mut found: HashMap<[u32; 3], TriPair> = HashMap::new();
+    for pair_a in pairs.iter() {
+        let coin = pair_a.base();
+        if !scanned(&coin) {
+            continue
+        }
+        for pair_b in by_quote.get(&coin).into_iter().flatten() {
+            if pair_b.base() == pair_a.quote() {
+                continue
+            }
+            let bridge = match bridges.get(&format!("{}/{}", pair_a.quote(), pair_b.base())) {
+                Some(bridge) => *bridge,
+                None => continue,
+            };
+            let tp = TriPair::new(coin.clone(), vec![pair_a, *pair_b, bridge]);
+            let mut key = tp.pairs;
+            key.sort();
+            match found.get(&key) {
+                Some(old) if old.name <= tp.name => {}
+                _ => { found.insert(key, tp); }
+            }
+        }
     }
+
+    found.into_values().collect()
 }
 
 // 根据一个币种和该币种的交易对列表, 结合 base symbols, 得到所有的 三角套利对
@@ -355,31 +513,25 @@ pub fn find_coin_tri_pairs(
         return None
     }
 
-    for (i, pair_a) in pairs.into_iter().enumerate() {
-        if i == pairs_len {
-            break
-        }
-        for j in (i+1)..pairs_len {
-            let pair_b = pairs[j];
+    for (i, pair_a) in pairs.iter().enumerate() {
+        for pair_b in &pairs[i+1..] {
             let base_a = pair_a.quote().clone().to_string();
             let base_b = pair_b.quote().clone().to_string();
             let c1 = base_a.clone() + "/" + base_b.as_str();
             let c2 = base_b + "/" + base_a.as_str();
-            let cs: &TradingPair;
-
-            if bs_map.contains_key(c1.as_str()) {
-                cs = bs_map[c1.as_str()];
-            } else if bs_map.contains_key(c2.as_str()) {
-                cs = bs_map[c2.as_str()];
+            let cs: &TradingPair = if let Some(cs) = bs_map.get(c1.as_str()) {
+                cs
+            } else if let Some(cs) = bs_map.get(c2.as_str()) {
+                cs
             } else {
                 // println!("not found symbol {} {} in base map", c1, c2);
                 continue
-            }
-            let tp = TriPair::new(coin.clone().to_string(), vec![*pair_a, pair_b, cs]);
+            };
+            let tp = TriPair::new(coin.to_string(), vec![*pair_a, *pair_b, cs]);
             tri_pairs.push(tp);
         }
     }
-    if tri_pairs.len() == 0 {
+    if tri_pairs.is_empty() {
         return None
     }
 
@@ -1,15 +1,32 @@
 use std::collections::HashMap;
-use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use chrono::prelude::Local;
+use chrono::prelude::{Local, Utc};
 use binance::api::*;
 use binance::general::*;
 use binance::model::*;
+use log::{error, info, warn};
 use crate::tri_pair::Ticker;
-use crate::tri_pair::{Profit, TriPair, derive_tri_pairs, to_tri_angle_symbol, TX_FEE};
-use crate::trading_pair::TradingPair;
+use crate::tri_pair::{Profit, TriPair, derive_all_tri_pairs, derive_tri_pairs, to_tri_angle_index};
+use crate::trading_pair::{PairFilters, TradingPair};
 use crate::config::Configuration;
-use crate::ticker_cache::start_best_ticker;
+use crate::cycle::{spawn_discovery, CurrencyGraph, Cycle, CycleIndex, CycleProfit};
+use crate::executor::{self, Executor, Outcome};
+use crate::logger;
+use crate::error::{parse_f64, Result};
+use crate::market_data::{Backoff, MarketDataSource};
+use crate::metrics::{self, Metrics};
+use crate::notifier::Notifier;
+use crate::commands::{self, Command};
+use crate::shutdown::Shutdown;
+use crate::sink::{self, SharedSink};
+use crate::tracker::{Opportunity, OpportunityTracker};
+use crate::depth::{best_depth_profit, eval_depth, BinancePartialDepth, Depth, DepthBook};
+use crate::sizing::Sizing;
+use crate::ticker_cache::BinanceBookTicker;
 
 // 包含所有的交易对及三角交易对
 // 交易对和三角组合的对应关系, 例如 btc/usdt eth/usdt eth/btc
@@ -19,38 +36,99 @@ use crate::ticker_cache::start_best_ticker;
 pub struct TriAngleArb {
     // id_symbol: HashMap<u32, String>, // id 和交易对的对应关系
     symbol_id: HashMap<String, u32>, // 交易对对应的 id
-    id_pairs: HashMap<u32, TradingPair>,
-    angles: HashMap<u32, Vec<TriPair>>,  // TradingPair 指向 pairs
+    asset_pairs: HashMap<String, HashMap<String, u32>>, // base -> quote -> 交易对 id, 换算收益时按资产查找, 不拼接字符串
+    id_pairs: HashMap<u32, TradingPair>, // 交易对的静态信息, 最新 ticker 在 tickers 中
+    tickers: Vec<Ticker>, // 以交易对 id 为下标的最新 ticker
+    triangles: Vec<TriPair>, // 每个三角套利组合只保存一份
+    angles: Vec<Vec<usize>>, // 以交易对 id 为下标, 该交易对关联的三角套利组合在 triangles 中的下标
+    depth: Option<DepthBook>, // 多档深度, 设置后按深度计算最优成交量
+    sizing: Sizing,
+    base_quotes: Vec<String>,
+    report_currency: String, // 收益换算的计价资产
+    graph: CurrencyGraph, // 所有交易对组成的货币图, 用于搜索 N 腿套利环
+    cycles: CycleIndex, // 交易对 -> 已发现的套利环
+    shutdown: Shutdown, // 置位后扫描循环退出
+    age_threshold: i64, // trading_age_threshold, 毫秒; 任一腿的 ticker 超过该时间未更新时跳过该三角组合, 0 不检查
+    stale: usize, // 因 ticker 过期而跳过的三角组合计算次数
+    tracker: OpportunityTracker, // 扫描循环中收益为正的机会, 每个机会结束时输出一次
+    cycle_tracker: OpportunityTracker, // 收益比例超过阈值的套利环, 与三角组合一样只在开始和结束时输出
+    notifier: Option<Notifier>, // Telegram 通知, 扫描开始时按配置创建
+    commands: Option<Arc<Mutex<Receiver<Command>>>>, // Telegram 命令, 扫描开始时按配置监听; Receiver 不能 Clone, 用 Mutex 包装
+    paused: bool, // /pause 后不执行交易, 继续扫描
+    subscribed: usize, // 订阅的交易对数量
+    last_tick_ms: i64, // 最后收到 ticker 的本地时间, 毫秒
+    metrics: Option<Metrics>, // Prometheus 指标, 扫描开始时按配置创建并监听 metrics_addr
+    sinks: Vec<SharedSink>, // 机会开始时写入, 未添加时扫描开始时按配置创建
+    config: Configuration,
 }
 
+// 重新搜索套利环的间隔
+const CYCLE_DISCOVER_MS: i64 = 5000;
+// 超过该时间未再被搜索到的套利环从索引中删除
+const CYCLE_EXPIRE_MS: i64 = 60000;
+// 报告因 ticker 过期而跳过的三角组合数量的间隔
+const STALE_REPORT_MS: i64 = 60000;
+// 获取交易所信息的最多尝试次数
+const EXCHANGE_INFO_RETRIES: usize = 5;
+// 等待 ticker 的超时时间, 超时后检查退出标志
+const RECV_TIMEOUT: Duration = Duration::from_millis(200);
+
 pub fn get_symbol_id(s: &Symbol) -> String {
 	format!("{}/{}", s.base_asset.clone(), s.quote_asset.clone())
 }
 
 impl TriAngleArb {
-    pub fn new(config_path: &str) -> Self {
-        let config: Configuration = Configuration::new(config_path);
-        let pairs = get_pairs(config.fee.unwrap_or(TX_FEE));
-        println!("got pairs: {}", pairs.len());
+    // 读取配置并从交易所获取交易对, 配置错误或多次重试后仍无法获取交易对时返回错误
+    pub fn new(config_path: &str) -> Result<Self> {
+        let config: Configuration = Configuration::new(config_path)?;
+        TriAngleArb::from_config(&config)
+    }
 
+    // 使用已读取的配置, 从交易所获取交易对
+    pub fn from_config(config: &Configuration) -> Result<Self> {
+        let pairs = get_pairs(config)?;
+        info!(pairs = pairs.len(); "got pairs");
+
+        Ok(TriAngleArb::with_pairs(config, pairs))
+    }
+
+    // 使用给定的交易对列表构造, 不访问交易所接口 (回放、模拟、测试)
+    pub fn with_pairs(config: &Configuration, pairs: Vec<TradingPair>) -> Self {
         let symbol_id: HashMap<String, u32> = pairs.iter().map(|x| (x.text(), x.get_symbol_idx())).collect();
-        let base_quotes: Vec<String> = config.base_quotes.clone().unwrap_or(vec!["BTC".to_string(), "USDT".to_string()]);
+        let mut asset_pairs: HashMap<String, HashMap<String, u32>> = HashMap::new();
+        for p in &pairs {
+            asset_pairs.entry(p.base()).or_default().insert(p.quote(), p.get_symbol_idx());
+        }
+        let base_quotes: Vec<String> = config.base_quotes.clone().unwrap_or_else(|| vec!["BTC".to_string(), "USDT".to_string()]);
         // let id_symbol: HashMap<u32, String> = pairs.iter().map(|x| (x.get_symbol_idx(), x.text())).collect();
 
-        let tri_pairs_map = derive_tri_pairs(
-            &pairs,
-            &base_quotes, 
-            None, 
-            config.exclude_coins.clone()
-        );
+        let tri_pairs_map = if config.all_triangles.unwrap_or(false) {
+            derive_all_tri_pairs(&pairs, config.allow_coins.clone(), config.exclude_coins.clone())
+        } else {
+            derive_tri_pairs(
+                &pairs,
+                &base_quotes, 
+                config.allow_coins.clone(), 
+                config.exclude_coins.clone()
+            )
+        };
+        let graph = CurrencyGraph::new(&pairs);
+        let size = pairs.iter().map(|x| x.get_symbol_idx() as usize + 1).max().unwrap_or(0);
         let id_pairs: HashMap<u32, TradingPair> = 
                 pairs
-                .iter()
-                .map(|x| (x.get_symbol_idx(), x.clone()))
+                .into_iter()
+                .map(|x| (x.get_symbol_idx(), x))
                 .collect(); // HashMap::new();
 
-        let angles = to_tri_angle_symbol(&tri_pairs_map); // &base_quotes, &id_pairs, &tri_pairs_map);
-        println!("tri-angles length: {} {}", tri_pairs_map.len(), angles.len());
+        let (triangles, angles) = to_tri_angle_index(&tri_pairs_map, size);
+        // 配置摘要, 不包含 api_key/api_secret
+        info!(target: "config", coins = tri_pairs_map.len(), triangles = triangles.len(),
+            investment_base = config.investment_base.as_str(), investment_min = config.investment_min,
+            investment_max = config.investment_max, profit_threshold = config.trading_profit_threshold,
+            age_threshold = config.trading_age_threshold, trading_enabled = config.trading_enabled,
+            paper_trading = config.paper_trading.unwrap_or(true), depth_enabled = config.depth_enabled.unwrap_or(false),
+            depth_size = config.depth_size,
+            report_currency = config.report_currency().as_str(); "configuration loaded");
         // println!("tri-angles coins: {:?}", tri_pairs_map.keys());
         // println!("tri-pairs ids: {:?}", angles.keys().fold("".to_string(), |mut acct, id| {
         //     let p = id_pairs.get(id).unwrap();
@@ -63,25 +141,396 @@ impl TriAngleArb {
         TriAngleArb {
             // id_symbol,
             symbol_id,
+            asset_pairs,
             id_pairs,
+            tickers: vec![Ticker::default(); size],
+            triangles,
             angles,
+            depth: None,
+            sizing: Sizing::new(config),
+            base_quotes,
+            report_currency: config.report_currency(),
+            graph,
+            cycles: CycleIndex::default(),
+            shutdown: Shutdown::new(),
+            age_threshold: config.trading_age_threshold as i64,
+            stale: 0,
+            tracker: OpportunityTracker::new(0.0),
+            cycle_tracker: OpportunityTracker::new(0.0),
+            notifier: None,
+            commands: None,
+            paused: false,
+            subscribed: 0,
+            last_tick_ms: 0,
+            metrics: None,
+            sinks: vec![],
+            config: config.clone(),
+        }
+    }
+
+    // 使用外部的退出标志, 例如 Shutdown::on_signal
+    pub fn set_shutdown(&mut self, shutdown: Shutdown) {
+        self.shutdown = shutdown;
+    }
+
+    // 退出标志, 可在其他线程中 trigger 使扫描循环退出
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    // 根据 BASE/QUOTE 得到交易对的 id
+    pub fn symbol_idx(&self, symbol_id: &str) -> Option<u32> {
+        self.symbol_id.get(symbol_id).copied()
+    }
+
+    pub fn get_pair(&self, idx: u32) -> Option<&TradingPair> {
+        self.id_pairs.get(&idx)
+    }
+
+    // 所有交易对, 按 id 排序
+    pub fn pairs(&self) -> Vec<TradingPair> {
+        let mut pairs: Vec<TradingPair> = self.id_pairs.values().cloned().collect();
+        pairs.sort_by_key(|x| x.get_symbol_idx());
+        pairs
+    }
+
+    // 更新交易对的最新 ticker, 更新 id 小于当前 ticker 的乱序消息忽略
+    pub fn set_ticker(&mut self, tick: Ticker) {
+        if let Some(t) = self.tickers.get_mut(tick.idx as usize) {
+            if tick.update_id > 0 && tick.update_id < t.update_id {
+                return
+            }
+            *t = tick;
+        }
+    }
+
+    // 因 ticker 过期而跳过的三角组合计算次数
+    pub fn stale_count(&self) -> usize {
+        self.stale
+    }
+
+    // 使用指定的通知器, 例如测试时指向本地 Bot API 服务
+    pub fn set_notifier(&mut self, notifier: Option<Notifier>) {
+        self.notifier = notifier;
+    }
+
+    // 未设置通知器时按配置创建
+    fn init_notifier(&mut self) {
+        if self.notifier.is_none() {
+            self.notifier = Notifier::from_config(&self.config);
+        }
+    }
+
+    // 使用指定的指标, 例如测试时直接读取
+    pub fn set_metrics(&mut self, metrics: Option<Metrics>) {
+        self.metrics = metrics;
+    }
+
+    pub fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+
+    // 未设置指标且配置了 metrics_addr 时创建指标并提供 /metrics, 监听失败时不记录指标
+    fn init_metrics(&mut self) {
+        if self.metrics.is_some() {
+            return
+        }
+        if let Some(addr) = &self.config.metrics_addr {
+            let m = Metrics::new();
+            match metrics::serve(addr, m.clone()) {
+                Ok(local) => {
+                    info!(addr:% = local; "metrics listening on http://{}/metrics", local);
+                    self.metrics = Some(m);
+                }
+                Err(e) => error!(addr = addr.as_str(), error:% = e; "metrics disabled, listen failed"),
+            }
+        }
+    }
+
+    // 添加机会的存储, 添加后不再按配置创建
+    pub fn add_sink(&mut self, sink: SharedSink) {
+        self.sinks.push(sink);
+    }
+
+    // 未添加存储时按配置创建, 创建失败时不写入
+    // 配置的 sink 无法创建时返回错误, 不启动扫描
+    fn init_sinks(&mut self) -> Result<()> {
+        if self.sinks.is_empty() {
+            self.sinks = sink::from_config(&self.config)?;
+        }
+        Ok(())
+    }
+
+    // 使用指定的命令队列, 例如测试时直接发送命令
+    pub fn set_commands(&mut self, commands: Option<Receiver<Command>>) {
+        self.commands = commands.map(|rx| Arc::new(Mutex::new(rx)));
+    }
+
+    // 未设置命令队列时按配置监听 Telegram 命令
+    fn init_commands(&mut self) {
+        if self.commands.is_none() {
+            self.commands = commands::listen(&self.config).map(|rx| Arc::new(Mutex::new(rx)));
+        }
+    }
+
+    // 是否已通过 /pause 暂停交易
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // 当前的 trading_profit_threshold, 百分比
+    pub fn profit_threshold(&self) -> f64 {
+        self.config.trading_profit_threshold
+    }
+
+    // 执行一条命令, 返回回复的文本
+    pub fn handle_command(&mut self, cmd: &Command) -> String {
+        match cmd {
+            Command::Status => {
+                let age = if self.last_tick_ms > 0 {
+                    format!("{}ms", Local::now().timestamp_millis() - self.last_tick_ms)
+                } else {
+                    "n/a".to_string()
+                };
+                format!("pairs: {}, triangles: {}, last tick: {}, open opportunities: {}, threshold: {}%, trading: {}",
+                    self.subscribed, self.triangles.len(), age, self.tracker.open_count(),
+                    self.config.trading_profit_threshold, if self.paused { "paused" } else { "running" })
+            }
+            Command::Top(n) => {
+                let top = self.top_profits(*n);
+                if top.is_empty() {
+                    return "no profitable triangles".to_string()
+                }
+                top.iter()
+                    .map(|p| format!("{} rev={}: ratio {:.4}%, profit {} {} ({} {})",
+                        p.name, p.rev, p.ratio * 100.0, p.profit, p.asset, p.value, self.report_currency))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            }
+            Command::Pause => {
+                self.paused = true;
+                "trading paused".to_string()
+            }
+            Command::Resume => {
+                self.paused = false;
+                "trading resumed".to_string()
+            }
+            Command::Threshold(x) if *x <= 0.0 || x.is_nan() => format!("invalid threshold {}: must be positive", x),
+            Command::Threshold(x) => {
+                let old = self.config.trading_profit_threshold;
+                self.config.trading_profit_threshold = *x;
+                format!("trading_profit_threshold: {}% -> {}%", old, x)
+            }
+            Command::LogLevel(level) => {
+                let old = logger::level();
+                logger::set_level(*level);
+                format!("log level: {} -> {}", old, level)
+            }
+            Command::Help => commands::HELP.to_string(),
+        }
+    }
+
+    // 当前收益为正的三角组合, 按换算为 report_currency 的收益从大到小, 最多 n 个; ticker 过期的组合不计
+    // 有无法换算的组合时按 ratio 排序
+    pub fn top_profits(&self, n: usize) -> Vec<Profit> {
+        let mut profits = Vec::with_capacity(self.triangles.len());
+        self.profits(self.triangles.iter(), Utc::now().timestamp_millis(), &mut profits);
+        profits.retain(|p| p.profit > 0.0);
+        if profits.iter().all(|p| p.has_value()) {
+            profits.sort_by(|a, b| b.value.partial_cmp(&a.value).unwrap_or(std::cmp::Ordering::Equal));
+        } else {
+            profits.sort_by(|a, b| b.ratio.partial_cmp(&a.ratio).unwrap_or(std::cmp::Ordering::Equal));
+        }
+        profits.truncate(n);
+        profits
+    }
+
+    // 处理队列中的所有命令, 回复通过通知器发送, 没有通知器时只输出到日志
+    fn poll_commands(&mut self) {
+        let cmds: Vec<Command> = match &self.commands {
+            Some(rx) => rx.lock().unwrap().try_iter().collect(),
+            None => return,
+        };
+        for cmd in cmds {
+            let reply = self.handle_command(&cmd);
+            info!(command:? = cmd; "{}", reply);
+            if let Some(notifier) = &self.notifier {
+                notifier.reply(&reply);
+            }
+        }
+    }
+
+    // 机会结束时输出一次
+    fn log_closed(&self, op: &Opportunity) {
+        info!(target: "opportunity", event = "closed", triangle = op.name.as_str(), rev = op.rev,
+            duration_ms = op.duration_ms(), ticks = op.ticks, peak_ratio = op.peak_ratio, peak_profit = op.peak_profit,
+            asset = op.asset.as_str(), peak_value = op.peak_value, currency = self.report_currency.as_str();
+            "{} {}", op, self.report_currency);
+    }
+
+    fn log_cycle_closed(&self, op: &Opportunity) {
+        info!(target: "opportunity", event = "closed", cycle = op.name.as_str(), duration_ms = op.duration_ms(),
+            ticks = op.ticks, peak_ratio = op.peak_ratio;
+            "cycle {} lasted {}ms ({} ticks), peak ratio: {}", op.name, op.duration_ms(), op.ticks, op.peak_ratio);
+    }
+
+    // 扫描循环中正在持续的机会
+    pub fn tracker(&self) -> &OpportunityTracker {
+        &self.tracker
+    }
+
+    // 扫描循环中收益比例超过阈值的套利环
+    pub fn cycle_tracker(&self) -> &OpportunityTracker {
+        &self.cycle_tracker
+    }
+
+    // 三角组合中是否有腿的 ticker 超过 trading_age_threshold 毫秒未更新, 接收时间未知的腿不检查
+    fn is_stale(&self, tp: &TriPair, now: i64) -> bool {
+        self.age_threshold > 0 && tp.pairs.iter().any(|p| {
+            let ts = self.tickers[*p as usize].ts;
+            ts > 0 && now - ts > self.age_threshold
+        })
+    }
+
+    // 交易对的最新 ticker
+    pub fn ticker(&self, idx: u32) -> Option<&Ticker> {
+        self.tickers.get(idx as usize)
+    }
+
+    // 以交易对 id 为下标的所有最新 ticker
+    pub fn tickers(&self) -> &[Ticker] {
+        &self.tickers
+    }
+
+    // 使用多档深度计算收益, 深度由数据源写入
+    pub fn set_depth_book(&mut self, book: DepthBook) {
+        self.depth = Some(book);
+    }
+
+    // 搜索套利环的最多腿数, 未配置或小于 3 时不搜索
+    fn cycle_max_legs(&self) -> Option<usize> {
+        self.config.cycle_max_legs.filter(|n| *n >= 3)
+    }
+
+    // 以最新 ticker 在货币图中搜索 3 到 cycle_max_legs 条腿的套利环, 加入交易对索引, 返回新发现的环数量
+    // 扫描循环在后台线程中搜索, 结果同样经过 add_cycles
+    pub fn discover_cycles(&mut self) -> usize {
+        let max_legs = match self.cycle_max_legs() {
+            Some(n) => n,
+            None => return 0,
+        };
+        let found = self.graph.find_cycles(&self.tickers, 3, max_legs);
+        self.add_cycles(&found, Local::now().timestamp_millis())
+    }
+
+    // 加入 ts 时搜索到的环, 删除过期的环, 返回新加入的环数量
+    fn add_cycles(&mut self, found: &[Cycle], ts: i64) -> usize {
+        let added = self.cycles.add(found, ts);
+        let expired = self.cycles.expire(ts - CYCLE_EXPIRE_MS);
+        if expired > 0 {
+            info!(cycles = expired, total = self.cycles.len(); "expired cycles");
+        }
+        added
+    }
+
+    // 交易对关联的套利环
+    pub fn cycles(&self, idx: u32) -> Option<&Vec<Cycle>> {
+        self.cycles.get(idx)
+    }
+
+    // 重新计算交易对关联的套利环, 收益比例达到 threshold 时开始跟踪, 低于时结束, 每个环只在开始和结束时输出
+    // 返回本次开始的环数量
+    pub fn track_cycles(&mut self, idx: u32, threshold: f64, now: i64) -> usize {
+        let profits = match self.cycles.get(idx) {
+            Some(cycles) => self.on_ticker_cycles(cycles),
+            None => return 0,
+        };
+        let mut opened = 0;
+        for cp in profits {
+            // 低于阈值的收益按 0 处理, 结束正在持续的环
+            let ratio = if cp.ratio >= threshold { cp.ratio } else { 0.0 };
+            let profit = Profit { name: cp.name.clone(), ratio, profit: ratio, ..Profit::default() };
+            let update = self.cycle_tracker.update(&cp.name, &profit, now);
+            if update.opened {
+                opened += 1;
+                info!(target: "opportunity", event = "opened", cycle = cp.name.as_str(), legs = cp.legs, ratio = cp.ratio;
+                    "cycle {} opened", cp.name);
+            }
+            for op in update.closed {
+                self.log_cycle_closed(&op);
+            }
+        }
+        opened
+    }
+
+    // 按最新 ticker 计算套利环的收益比例, 与 cycles 顺序一致
+    pub fn on_ticker_cycles(&self, cycles: &[Cycle]) -> Vec<CycleProfit> {
+        cycles.iter().map(|c| CycleProfit {
+            name: c.name.clone(),
+            legs: c.legs.len(),
+            ratio: c.ratio(&self.tickers).unwrap_or(0.0),
+        }).collect()
+    }
+
+    // 交易对关联的三角套利组合在 triangles 中的下标
+    pub fn angle_ids(&self, idx: u32) -> &[usize] {
+        self.angles.get(idx as usize).map_or(&[], |ids| ids.as_slice())
+    }
+
+    pub fn triangle(&self, id: usize) -> &TriPair {
+        &self.triangles[id]
+    }
+
+    // 交易对关联的三角套利组合的拷贝, 扫描循环使用 angle_ids / on_symbol 避免拷贝
+    pub fn angles(&self, idx: u32) -> Option<Vec<TriPair>> {
+        let ids = self.angle_ids(idx);
+        if ids.is_empty() {
+            return None
+        }
+        Some(ids.iter().map(|id| self.triangles[*id].clone()).collect())
+    }
+
+    // 1 个 from 可以换得多少 to: 卖出用买一价, 买入用卖一价
+    fn rate(&self, from: &str, to: &str) -> Option<f64> {
+        let tick = |base: &str, quote: &str| self.asset_pairs.get(base)
+            .and_then(|quotes| quotes.get(quote))
+            .and_then(|id| self.ticker(*id));
+        if let Some(tick) = tick(from, to) {
+            if tick.bb[0] > 0.0 {
+                return Some(tick.bb[0])
+            }
+        }
+        if let Some(tick) = tick(to, from) {
+            if tick.ba[0] > 0.0 {
+                return Some(1.0 / tick.ba[0])
+            }
+        }
+        None
+    }
+
+    // 按最新 ticker 把 amount 个 from 换算为 to, 通过直接的交易对或经过 base_quotes 中的一个资产
+    pub fn convert(&self, amount: f64, from: &str, to: &str) -> Option<f64> {
+        if from == to {
+            return Some(amount)
         }
+        if let Some(rate) = self.rate(from, to) {
+            return Some(amount * rate)
+        }
+        self.base_quotes.iter()
+            .filter(|mid| *mid != from && *mid != to)
+            .find_map(|mid| Some(amount * self.rate(from, mid)? * self.rate(mid, to)?))
     }
 
-    // 计算该 ticker 造成的收益变动
-    pub fn on_ticker(&self, angles: &Vec<TriPair>) -> Profit {
+    pub fn report_currency(&self) -> &str {
+        &self.report_currency
+    }
+
+    // 计算该 ticker 造成的收益变动, 按换算为 report_currency 的收益选择最优, 无法换算时按 ratio
+    pub fn on_ticker(&mut self, angles: &[TriPair]) -> Profit {
         let mut best_profit: Profit = Profit::default();
-        let mut profit;
-
-        for tp in angles {
-            let p0 = tp.pairs[0];
-            let p1 = tp.pairs[1];
-            let p2 = tp.pairs[2];
-            let t0 = self.id_pairs.get(&p0).unwrap();
-            let t1 = self.id_pairs.get(&p1).unwrap();
-            let t2 = self.id_pairs.get(&p2).unwrap();
-            profit = tp.calc_profit(&t0.tick, &t1.tick, &t2.tick, false);
-            if profit.profit > best_profit.profit {
+
+        for profit in self.on_ticker_all(angles) {
+            if profit.better_than(&best_profit) {
                 best_profit = profit
             }
         }
@@ -89,48 +538,368 @@ impl TriAngleArb {
         best_profit
     }
 
-    pub fn start(&mut self) {
-        let symbol_id: HashMap<String, u32> = self.id_pairs.iter().map(|(id, tp)| (tp.get_symbol(), *id)).collect();
-        let ticker_rx = start_best_ticker(symbol_id);
+    // 计算每个三角套利组合的收益, 与 angles 顺序一致; ticker 过期或未初始化的组合为 Profit::default(), name 为空
+    pub fn on_ticker_all(&mut self, angles: &[TriPair]) -> Vec<Profit> {
+        let mut profits = Vec::with_capacity(angles.len());
+        self.stale += self.profits(angles.iter(), Utc::now().timestamp_millis(), &mut profits);
+        profits
+    }
+
+    // 交易对 ticker 变化后重新计算关联的三角套利组合, 返回换算为 report_currency 后最优的收益
+    // 按下标读取三角组合和 ticker, 不拷贝 TriPair; 任一腿 ticker 过期的组合跳过并计入 stale_count
+    pub fn on_symbol(&mut self, idx: u32) -> Profit {
+        let now = Utc::now().timestamp_millis();
+        let mut stale = 0;
+        let book = self.depth.as_ref().map(|book| book.read().unwrap());
+        let mut best_profit: Profit = Profit::default();
+        for id in self.angle_ids(idx) {
+            let tp = &self.triangles[*id];
+            if self.is_stale(tp, now) {
+                stale += 1;
+                continue
+            }
+            let profit = self.profit_of(tp, book.as_deref());
+            if profit.better_than(&best_profit) {
+                best_profit = profit
+            }
+        }
+        drop(book);
+        self.stale += stale;
+        best_profit
+    }
+
+    // 与 on_symbol 相同, 返回每个三角套利组合的收益, 与 angle_ids 顺序一致
+    pub fn on_symbol_all(&mut self, idx: u32) -> Vec<Profit> {
+        let mut profits = Vec::with_capacity(self.angle_ids(idx).len());
+        self.on_symbol_into(idx, Utc::now().timestamp_millis(), &mut profits);
+        profits
+    }
+
+    // 与 on_symbol_all 相同, 结果写入 out (先清空); now 为本次 ticker 的时间 (ms)
+    // 扫描循环在每个 ticker 间复用 out, 时钟只读取一次, 深度的读锁在所有组合间只获取一次
+    pub fn on_symbol_into(&mut self, idx: u32, now: i64, out: &mut Vec<Profit>) {
+        out.clear();
+        let ids = &self.angles[idx as usize];
+        self.stale += self.profits(ids.iter().map(|id| &self.triangles[*id]), now, out);
+    }
+
+    // 每个三角组合的收益追加到 out, ticker 过期或未初始化的组合为 Profit::default(), name 为空; 返回过期的组合数量
+    fn profits<'a, I: Iterator<Item = &'a TriPair>>(&self, tps: I, now: i64, out: &mut Vec<Profit>) -> usize {
+        let mut stale = 0;
+        let book = self.depth.as_ref().map(|book| book.read().unwrap());
+        out.extend(tps.map(|tp| {
+            if self.is_stale(tp, now) {
+                stale += 1;
+                return Profit::default()
+            }
+            self.profit_of(tp, book.as_deref())
+        }));
+        stale
+    }
+
+    fn profit_of(&self, tp: &TriPair, book: Option<&HashMap<u32, Depth>>) -> Profit {
+        let [p0, p1, p2] = tp.pairs;
+        let t = &self.tickers;
+        let mut profit = tp.calc_profit(&t[p0 as usize], &t[p1 as usize], &t[p2 as usize], false);
+        let books = book.and_then(|book| match (book.get(&p0), book.get(&p1), book.get(&p2)) {
+            (Some(d0), Some(d1), Some(d2)) => Some([d0, d1, d2]),
+            _ => None,
+        });
+        if let Some(books) = books {
+            self.apply_depth(tp, &mut profit, books);
+        }
+        if profit.profit > 0.0 {
+            let pairs = [&self.id_pairs[&p0], &self.id_pairs[&p1], &self.id_pairs[&p2]];
+            self.apply_sizing(tp, &mut profit, pairs, books);
+        }
+        if profit.profit != 0.0 {
+            profit.value = self.convert(profit.profit, &profit.asset, &self.report_currency).unwrap_or(0.0);
+        }
+        profit
+    }
+
+    // 在投资范围内选择成交量, 收益换算为 investment_base, 便于比较不同的三角组合
+    // investment_base 不在该三角组合中时保持原结果
+    fn apply_sizing(&self, tp: &TriPair, profit: &mut Profit, pairs: [&TradingPair; 3], books: Option<[&Depth; 3]>) {
+        if !self.sizing.applies(pairs) {
+            return
+        }
+        let rev = profit.rev;
+        let ticks = profit.tickers.clone();
+        let tickers = [&ticks[0], &ticks[1], &ticks[2]];
+        let sized = match books {
+            Some(books) => self.sizing.size(tp, rev, pairs, tickers, |amount| eval_depth(tp, rev, books, amount)),
+            None => self.sizing.size(tp, rev, pairs, tickers, |amount| tp.exec_legs(rev, amount, tickers)),
+        };
+        match sized {
+            Some(sized) if sized.profit > 0.0 => {
+                profit.amount = sized.amount;
+                profit.asset = self.sizing.base().to_string();
+                profit.invest = sized.spend;
+                profit.profit = sized.profit;
+                profit.ratio = sized.profit / sized.spend;
+            }
+            _ => {
+                profit.profit = 0.0;
+                profit.ratio = 0.0;
+            }
+        }
+    }
+
+    // 用多档深度下的最优成交量替换只看最优一档的结果
+    fn apply_depth(&self, tp: &TriPair, profit: &mut Profit, books: [&Depth; 3]) {
+        match best_depth_profit(tp, books) {
+            Some(dp) => {
+                profit.name = tp.name.clone();
+                profit.rev = dp.rev;
+                profit.asset = tp.start_asset(dp.rev);
+                profit.amount = dp.amount;
+                profit.profit = dp.profit;
+                profit.ratio = dp.profit / dp.cost;
+                profit.tickers = [books[0].ticker(), books[1].ticker(), books[2].ticker()];
+            }
+            None => {
+                profit.profit = 0.0;
+                profit.ratio = 0.0;
+            }
+        }
+    }
 
-        println!("ws bookticker subscribed!");
+    // 默认只订阅最优价格, depth_enabled 时订阅 depth_size 档深度
+    // 配置的 sink 无法创建时返回错误
+    pub fn start(&mut self) -> Result<()> {
+        self.init_notifier();
+        self.init_commands();
+        self.init_metrics();
+        self.init_sinks()?;
+        if self.config.depth_enabled.unwrap_or(false) && self.config.depth_size > 0 {
+            let mut source = BinancePartialDepth::new(self.config.depth_size)
+                .with_notifier(self.notifier.clone())
+                .with_metrics(self.metrics.clone());
+            self.set_depth_book(source.book());
+            self.start_with(&mut source)
+        } else {
+            self.start_with(&mut BinanceBookTicker::new().with_notifier(self.notifier.clone()).with_metrics(self.metrics.clone()))
+        }
+    }
+
+    // 从任意行情数据源扫描三角套利机会, 数据源结束 (队列断开) 或退出标志置位时返回
+    // trading_enabled 时使用配置对应的执行器执行超过阈值的机会; 配置的 sink 无法创建时返回错误, 不订阅行情
+    pub fn start_with<S: MarketDataSource>(&mut self, source: &mut S) -> Result<()> {
+        self.init_notifier();
+        self.init_commands();
+        self.init_metrics();
+        self.init_sinks()?;
+        let mut executor = executor::from_config(&self.config);
+        if let (Some(ex), Some(notifier)) = (executor.as_mut(), &self.notifier) {
+            ex.set_notifier(notifier.clone());
+        }
+        let executor = executor.as_mut().map(|ex| ex.as_mut() as &mut dyn Executor);
+        self.run(source, executor);
+        Ok(())
+    }
+
+    pub fn run<S: MarketDataSource>(&mut self, source: &mut S, mut executor: Option<&mut dyn Executor>) {
+        // 只订阅参与三角套利的交易对, 搜索套利环时订阅所有交易对
+        let all = self.cycle_max_legs().is_some();
+        let symbol_id: HashMap<String, u32> = self.id_pairs.iter()
+            .filter(|(id, _)| all || !self.angle_ids(**id).is_empty())
+            .map(|(id, tp)| (tp.get_symbol(), *id))
+            .collect();
+        self.subscribed = symbol_id.len();
+        let ticker_rx = source.subscribe(symbol_id);
+
+        info!(pairs = self.subscribed; "market data subscribed");
         self.wait_ticker_initialized(&ticker_rx, 10000);
         // println!("all symbol ticker initialized");
+        let mut last_discover: i64 = 0;
+        let discovery = self.cycle_max_legs().map(|n| spawn_discovery(self.graph.clone(), n));
+        let mut last_report = Local::now().timestamp_millis();
+        let mut reported_stale = self.stale;
+        // 每个 ticker 的收益写入同一个 Vec, 不重新分配
+        let mut profits: Vec<Profit> = Vec::new();
 
-        loop {
-            if let Ok(tick) = ticker_rx.try_recv() {
-                let idx = tick.idx;
-                if let Some(mut pair) = self.id_pairs.get_mut(&tick.idx) {
-                    pair.tick = tick;
+        while !self.shutdown.is_triggered() {
+            self.poll_commands();
+            let tick = match ticker_rx.recv_timeout(RECV_TIMEOUT) {
+                Ok(tick) => tick,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            let idx = tick.idx;
+            let received_ms = tick.ts;
+            let started = Instant::now();
+            self.set_ticker(tick);
+            let now = Local::now().timestamp_millis();
+            if let (Some(metrics), Some(pair)) = (&self.metrics, self.id_pairs.get(&idx)) {
+                metrics.ticker_received(pair.symbol());
+            }
+            self.last_tick_ms = now;
+            // /threshold 可在运行时修改阈值
+            let threshold = self.config.trading_profit_threshold / 100.0;
+            if now - last_report >= STALE_REPORT_MS {
+                if self.stale > reported_stale {
+                    warn!(discarded = self.stale - reported_stale, seconds = (now - last_report) / 1000, total = self.stale;
+                        "discarded stale triangles");
                 }
-                if let Some(angles) = self.angles.get(&idx) {
-                    let profit = self.on_ticker(angles);
-
-                    if profit.ratio > 0.0 {
-                        println!("tripair {} profitable: ratio: {} {} {}",
-                            self.id_pairs.get(&idx).unwrap().text(), profit.ratio, profit.amount, profit.profit);
+                last_report = now;
+                reported_stale = self.stale;
+            }
+            if let Some((snapshots, found)) = &discovery {
+                // 后台线程空闲时才发送快照, 搜索结果在之后的 ticker 中合并
+                if now - last_discover >= CYCLE_DISCOVER_MS && snapshots.try_send(self.tickers.clone()).is_ok() {
+                    last_discover = now;
+                }
+                while let Ok(cycles) = found.try_recv() {
+                    let added = self.add_cycles(&cycles, now);
+                    if added > 0 {
+                        info!(cycles = added; "discovered new cycles");
+                    }
+                }
+            }
+            if all {
+                self.track_cycles(idx, threshold, now);
+            }
+            if !self.angle_ids(idx).is_empty() {
+                // 每个三角组合的机会分别跟踪, 机会结束时输出一次
+                // 执行器使用超过阈值且尚未执行的机会中最优的一个, 每个机会只执行一次
+                let mut best: Option<Profit> = None;
+                self.on_symbol_into(idx, now, &mut profits);
+                for (k, p) in profits.drain(..).enumerate() {
+                    let tp = &self.triangles[self.angles[idx as usize][k]];
+                    // ticker 过期或未初始化时没有结果, 不影响已有的机会
+                    if p.name.is_empty() {
+                        continue
+                    }
+                    if let Some(metrics) = &self.metrics {
+                        metrics.triangle_evaluated(&tp.name, p.ratio, p.profit > 0.0 && p.ratio >= threshold);
+                    }
+                    let update = self.tracker.update(&tp.name, &p, now);
+                    if update.opened {
+                        info!(target: "opportunity", event = "opened", triangle = p.name.as_str(), rev = p.rev,
+                            symbol = self.id_pairs[&idx].symbol(), ratio = p.ratio, amount = p.amount, profit = p.profit,
+                            asset = p.asset.as_str(), value = p.value, currency = self.report_currency.as_str();
+                            "opportunity {} rev={} opened", p.name, p.rev);
+                    }
+                    // 机会持续期间收益比例第一次达到通知阈值时通知
+                    if let Some(notifier) = self.notifier.as_ref().filter(|n| p.ratio >= n.min_ratio()) {
+                        if self.tracker.get(&tp.name, p.rev).is_some_and(|op| !op.notified) {
+                            notifier.notify(&format!("opportunity {} rev={}: ratio {:.4}%, profit {} {} ({} {})",
+                                p.name, p.rev, p.ratio * 100.0, p.profit, p.asset, p.value, self.report_currency));
+                            self.tracker.mark_notified(&tp.name, p.rev);
+                        }
+                    }
+                    // 机会开始时写入一次, 超过阈值的收益每次都写入
+                    let above = p.profit > 0.0 && p.ratio >= threshold;
+                    if !self.sinks.is_empty() && (update.opened || above) {
+                        let symbols = [
+                            self.id_pairs[&tp.pairs[0]].symbol(),
+                            self.id_pairs[&tp.pairs[1]].symbol(),
+                            self.id_pairs[&tp.pairs[2]].symbol(),
+                        ];
+                        for sink in &self.sinks {
+                            let mut sink = sink.lock().unwrap();
+                            if update.opened {
+                                if let Err(e) = sink.record(&p, symbols) {
+                                    error!(triangle = p.name.as_str(), error:% = e; "record opportunity failed");
+                                }
+                            }
+                            if above {
+                                if let Err(e) = sink.record_profit(&p, symbols) {
+                                    error!(triangle = p.name.as_str(), error:% = e; "record profit failed");
+                                }
+                            }
+                        }
+                    }
+                    for op in update.closed {
+                        self.log_closed(&op);
+                    }
+                    let pending = p.profit > 0.0 && p.ratio >= threshold
+                        && self.tracker.get(&tp.name, p.rev).is_some_and(|op| !op.executed);
+                    if pending && best.as_ref().is_none_or(|b| p.better_than(b)) {
+                        best = Some(p);
+                    }
+                }
+                // 接收时间未知 (回放) 时只计算本次计算的时间
+                if let Some(metrics) = &self.metrics {
+                    let queued = if received_ms > 0 { (now - received_ms).max(0) as f64 / 1000.0 } else { 0.0 };
+                    metrics.observe_latency(queued + started.elapsed().as_secs_f64());
+                }
+                if let (Some(ex), Some(profit), false) = (&mut executor, best, self.paused) {
+                    let mut submitted = false;
+                    let found = self.angle_ids(idx).iter().map(|id| &self.triangles[*id]).find(|tp| tp.name == profit.name);
+                    if let Some(tp) = found {
+                        let pairs = [
+                            self.id_pairs.get(&tp.pairs[0]).unwrap(),
+                            self.id_pairs.get(&tp.pairs[1]).unwrap(),
+                            self.id_pairs.get(&tp.pairs[2]).unwrap(),
+                        ];
+                        submitted = match ex.execute(tp, &profit, pairs) {
+                            Outcome::Skipped => false,
+                            Outcome::Submitted => true,
+                            Outcome::Executed(exec) => {
+                                if let Some(notifier) = &self.notifier {
+                                    notifier.notify(&format!("executed {} rev={}: spent {} {} got {} {}, pnl {}",
+                                        tp.name, profit.rev, exec.start, exec.asset, exec.end, exec.asset, exec.pnl()));
+                                }
+                                true
+                            }
+                        };
+                    }
+                    // 未执行的机会保持待执行, 之后的 ticker 仍可再次尝试
+                    if submitted {
+                        self.tracker.mark_executed(&profit.name, profit.rev);
                     }
                 }
             }
         }
+
+        for op in self.tracker.finish() {
+            self.log_closed(&op);
+        }
+        for op in self.cycle_tracker.finish() {
+            self.log_cycle_closed(&op);
+        }
+        if self.stale > 0 {
+            warn!(total = self.stale; "discarded stale triangles in total");
+        }
+        for sink in &self.sinks {
+            if let Err(e) = sink.lock().unwrap().flush() {
+                error!(error:% = e; "flush opportunities failed");
+            }
+        }
+        if let Some(ex) = executor {
+            ex.shutdown();
+        }
     }
 
     // 等待所有的 symbol 全部初始化完成
     fn wait_ticker_initialized(&mut self, recv_rx: &Receiver<Ticker>, ms: i64) -> Vec<u32> {
-        let mut mt: HashMap<u32, bool> = self.angles.iter().map(|(idx, _)| (*idx, false)).collect();
+        let mut mt: HashMap<u32, bool> = (0..self.angles.len() as u32)
+            .filter(|idx| !self.angle_ids(*idx).is_empty())
+            .map(|idx| (idx, false))
+            .collect();
         let total = mt.len();
         let mut inited: usize = 0;
 
-        println!("total tri pairs: {}", total);
+        info!(pairs = total; "waiting for tickers");
         let start = Local::now().timestamp_millis();
         let mut now: i64;
 
-        loop {
-            if let Ok(tick) = recv_rx.try_recv() {
+        while !self.shutdown.is_triggered() {
+            let tick = match recv_rx.recv_timeout(RECV_TIMEOUT) {
+                Ok(tick) => Some(tick),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            if let Some(tick) = tick {
                 if let Some(val) = mt.get(&tick.idx) {
-                    if *val == false {
-                        mt.insert(tick.idx, true); // [&tick.idx] = true;
-                        inited = inited + 1;
+                    let first = !*val;
+                    let idx = tick.idx;
+                    self.set_ticker(tick);
+                    if first {
+                        mt.insert(idx, true); // [&tick.idx] = true;
+                        inited += 1;
                         // println!("initialized {}. pair {} {} ticker: asks: [{} {}] bids: [{} {}]",
                         //     inited, self.id_pairs.get(&tick.idx).unwrap().text(), tick.idx,
                         //     tick.ba[0], tick.ba[1], tick.bb[0], tick.bb[1]);
@@ -139,19 +908,17 @@ impl TriAngleArb {
                             return vec![];
                         }
                     }
-                    let mut pair = self.id_pairs.get_mut(&tick.idx).unwrap();
-                    pair.tick = tick
                 }
                 // pair.bids = tick.bb;
             }
             now = Local::now().timestamp_millis();
             if now - start > ms {
-                println!("time up, recv {}/{} pairs ticker", inited, total);
+                warn!(received = inited, pairs = total; "time up waiting for tickers");
                 break;
             }
         }
 
-        let uninited: Vec<u32> = mt.iter().filter(|(_, inited)| false == **inited).map(|x| *x.0 ).collect();
+        let uninited: Vec<u32> = mt.iter().filter(|(_, inited)| !**inited).map(|x| *x.0 ).collect();
         // println!("uninited {}: {:?}", total-inited, uninited);
 
         // panic!("stop");
@@ -159,43 +926,73 @@ impl TriAngleArb {
     }
 }
 
-fn get_pairs(fee: f64) -> Vec<TradingPair> {
-    let mut pairs: Vec<TradingPair> = Vec::new();
+// 获取交易所信息, 失败时按 Backoff 等待后重试, 最多 EXCHANGE_INFO_RETRIES 次
+fn exchange_info() -> Result<ExchangeInformation> {
     let general: General = Binance::new(None, None);
-    let result = match general.exchange_info() {
-      Ok(answer) => answer,
-      Err(e) => panic!("Error on getting exchange info: {}", e),
-    };
-    // symbol 与 id 对应关系
-    let mut symbol_id_map: HashMap<String, String> = HashMap::new();
+    let mut backoff = Backoff::default();
+    let mut attempt = 1;
+    loop {
+        match general.exchange_info() {
+            Ok(answer) => return Ok(answer),
+            Err(e) if attempt < EXCHANGE_INFO_RETRIES => {
+                let delay = backoff.next_delay();
+                warn!(attempt = attempt, retries = EXCHANGE_INFO_RETRIES, error:% = e, delay_ms = delay.as_millis() as u64;
+                    "get exchange info failed, retrying");
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// 交易对的 LotSize, MinNotional 过滤器
+fn parse_symbol_filters(symbol: &Symbol) -> Result<PairFilters> {
+    let mut filters = PairFilters::default();
+    for filter in &symbol.filters {
+        match filter {
+            Filters::LotSize { min_qty, max_qty, step_size } => {
+                filters.step = parse_f64("step size", step_size)?;
+                filters.min_qty = parse_f64("min qty", min_qty)?;
+                filters.max_qty = parse_f64("max qty", max_qty)?;
+            }
+            Filters::MinNotional { notional, min_notional, .. } => {
+                if let Some(v) = min_notional.as_ref().or(notional.as_ref()) {
+                    filters.min_notional = parse_f64("min notional", v)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(filters)
+}
+
+// 所有 TRADING 状态的交易对, 过滤器无法解析的交易对跳过
+fn get_pairs(config: &Configuration) -> Result<Vec<TradingPair>> {
+    let mut pairs: Vec<TradingPair> = Vec::new();
+    let result = exchange_info()?;
     let mut idx: u32 = 1;
     for symbol in &result.symbols {
       // Checks if symbol is currently trading
-      if symbol.status == "TRADING" {
-        symbol_id_map.insert(symbol.symbol.clone(), get_symbol_id(symbol));
-
-        let mut step: f64 = 0.0;
-        // Get step for this symbol
-        for filter in &symbol.filters {
-          if let Filters::LotSize {
-            min_qty: _,
-            max_qty: _,
-            step_size,
-          } = filter
-          {
-            step = step_size.parse().unwrap()
-          };
-        }
-        pairs.push(TradingPair::new(
-          idx,
-          symbol.symbol.to_string(),
-          symbol.base_asset.to_string(),
-          symbol.quote_asset.to_string(),
-          step,
-          fee,
-        ));
-        idx += 1;
+      if symbol.status != "TRADING" {
+        continue
       }
+      let filters = match parse_symbol_filters(symbol) {
+        Ok(filters) => filters,
+        Err(e) => {
+          warn!(symbol = symbol.symbol.as_str(), error:% = e; "skip symbol");
+          continue
+        }
+      };
+      pairs.push(TradingPair::new(
+        idx,
+        symbol.symbol.to_string(),
+        symbol.base_asset.to_string(),
+        symbol.quote_asset.to_string(),
+        filters.step,
+        config.pair_fee(&symbol.symbol),
+      ).with_filters(filters));
+      idx += 1;
     }
-    pairs
-}
\ No newline at end of file
+    Ok(pairs)
+}
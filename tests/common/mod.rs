@@ -0,0 +1,67 @@
+// 集成测试共用的交易对和行情数据源, 每个测试文件只用到其中一部分
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use triangle::market_data::MarketDataSource;
+use triangle::trading_pair::TradingPair;
+use triangle::tri_pair::{Profit, Ticker, TriPair};
+
+// 按顺序发送 (交易对, 卖一, 买一) 后断开队列
+pub struct VecSource {
+  pub tickers: Vec<(&'static str, [f64; 2], [f64; 2])>,
+}
+
+impl MarketDataSource for VecSource {
+  fn subscribe(&mut self, symbol_id_map: HashMap<String, u32>) -> Receiver<Ticker> {
+    let (tx, rx) = mpsc::channel();
+    for (symbol, ba, bb) in self.tickers.drain(..) {
+      let idx = *symbol_id_map.get(symbol).unwrap();
+      tx.send(Ticker { idx, ba, bb, ..Ticker::default() }).unwrap();
+    }
+    rx
+  }
+}
+
+// 发送初始 ticker 后保持队列不断开, 模拟不会结束的 ws 数据源
+pub struct OpenSource {
+  pub tx: Option<Sender<Ticker>>,
+}
+
+impl MarketDataSource for OpenSource {
+  fn subscribe(&mut self, symbol_id_map: HashMap<String, u32>) -> Receiver<Ticker> {
+    let (tx, rx) = mpsc::channel();
+    for idx in symbol_id_map.values() {
+      tx.send(Ticker { idx: *idx, ba: [1.0, 1.0], bb: [0.9, 1.0], ..Ticker::default() }).unwrap();
+    }
+    self.tx = Some(tx);
+    rx
+  }
+}
+
+// ETH/BTC ETH/USDT BTC/USDT, id 为 1 2 3, fees 为每个交易对的手续费乘数
+pub fn eth_pairs_with_fees(fees: [f64; 3]) -> Vec<TradingPair> {
+  vec![
+    TradingPair::new(1, "ETHBTC".to_string(), "ETH".to_string(), "BTC".to_string(), 0.001, fees[0]),
+    TradingPair::new(2, "ETHUSDT".to_string(), "ETH".to_string(), "USDT".to_string(), 0.001, fees[1]),
+    TradingPair::new(3, "BTCUSDT".to_string(), "BTC".to_string(), "USDT".to_string(), 0.00001, fees[2]),
+  ]
+}
+
+// 手续费均为 0.1% 的 ETH/BTC ETH/USDT BTC/USDT
+pub fn eth_pairs() -> Vec<TradingPair> {
+  eth_pairs_with_fees([0.999; 3])
+}
+
+// ETH/BTC ETH/USDT BTC/USDT 上正向有收益的机会: 以 0.05 BTC 买 ETH, 以 2100 USDT 卖出, 以 40000 USDT 买回 BTC
+pub fn eth_opportunity() -> (Vec<TradingPair>, TriPair, Profit) {
+  let pairs = eth_pairs();
+  let tp = TriPair::new("ETH".to_string(), vec![&pairs[0], &pairs[1], &pairs[2]]);
+  let profit = tp.calc_profit(
+    &Ticker { idx: 1, ba: [0.05, 10.0], bb: [0.0499, 10.0], ..Ticker::default() },
+    &Ticker { idx: 2, ba: [2100.5, 10.0], bb: [2100.0, 10.0], ..Ticker::default() },
+    &Ticker { idx: 3, ba: [40000.0, 5.0], bb: [39999.0, 5.0], ..Ticker::default() },
+    false,
+  );
+  (pairs, tp, profit)
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod all_triangles_tests {
+  use triangle::depth::{best_depth_profit, Depth};
+  use triangle::simulate::simulate;
+  use triangle::trading_pair::TradingPair;
+  use triangle::tri_pair::{derive_all_tri_pairs, derive_tri_pairs, Side, Ticker};
+
+  fn to_pairs(symbols: &[(&str, &str)]) -> Vec<TradingPair> {
+    symbols.iter().enumerate().map(|(i, (base, quote))| {
+      TradingPair::new(i as u32 + 1, format!("{}{}", base, quote), base.to_string(), quote.to_string(), 0.001, 0.999)
+    }).collect()
+  }
+
+  fn pairs() -> Vec<TradingPair> {
+    to_pairs(&[("ETH", "BNB"), ("ADA", "ETH"), ("ADA", "BNB"), ("ETH", "BTC"), ("BTC", "USDT"), ("ETH", "USDT")])
+  }
+
+  fn names(map: &std::collections::HashMap<String, Vec<triangle::tri_pair::TriPair>>) -> Vec<String> {
+    let mut names: Vec<String> = map.values().flatten().map(|tp| tp.name.clone()).collect();
+    names.sort();
+    names
+  }
+
+  #[test]
+  fn derive_all_tri_pairs_test() {
+    let pairs = pairs();
+    let base_quotes = vec!["BTC".to_string(), "USDT".to_string(), "BUSD".to_string()];
+
+    // 只有 BTC/USDT 可以作为桥, ADA 的三角组合被遗漏
+    let map = derive_tri_pairs(&pairs, &base_quotes, None, None);
+    assert_eq!(names(&map), vec!["ETH-BTC-USDT"]);
+
+    let map = derive_all_tri_pairs(&pairs, None, None);
+    assert_eq!(names(&map), vec!["ADA-ETH-BNB", "ETH-BTC-USDT"]);
+    let ada = &map["ADA"][0];
+    assert_eq!(ada.pairs_name[2], "ETH/BNB");
+  }
+
+  #[test]
+  fn allow_deny_test() {
+    let pairs = pairs();
+    let map = derive_all_tri_pairs(&pairs, Some(vec!["ADA".to_string()]), None);
+    assert_eq!(names(&map), vec!["ADA-ETH-BNB"]);
+
+    let map = derive_all_tri_pairs(&pairs, None, Some(vec!["ADA".to_string()]));
+    assert_eq!(names(&map), vec!["ETH-BTC-USDT"]);
+  }
+
+  #[test]
+  fn cyclic_triangle_test() {
+    // ETH/BTC BTC/DAI DAI/ETH: 每个资产只作为一次 base
+    let pairs = to_pairs(&[("ETH", "BTC"), ("BTC", "DAI"), ("DAI", "ETH")]);
+    let map = derive_all_tri_pairs(&pairs, None, None);
+    assert_eq!(names(&map), vec!["BTC-DAI-ETH"]);
+    let tp = &map["BTC"][0];
+    assert!(tp.is_cyclic());
+    assert_eq!(tp.pairs_name, ["BTC/DAI".to_string(), "ETH/BTC".to_string(), "DAI/ETH".to_string()]);
+    // 正向 DAI -> BTC -> ETH -> DAI 都是买入, 反向 ETH -> BTC -> DAI -> ETH 都是卖出
+    assert!(tp.legs(false).iter().all(|(_, side)| *side == Side::SideBuy));
+    assert!(tp.legs(true).iter().all(|(_, side)| *side == Side::SideSell));
+    assert_eq!(tp.start_asset(false), "DAI");
+    assert_eq!(tp.start_asset(true), "ETH");
+    // 只有 BTC/DAI 可以作为桥时从 ETH 出发
+    let base_quotes = vec!["BTC".to_string(), "DAI".to_string()];
+    assert_eq!(names(&derive_tri_pairs(&pairs, &base_quotes, None, None)), vec!["ETH-BTC-DAI"]);
+    // BTC 被排除时从其他资产出发
+    assert_eq!(names(&derive_all_tri_pairs(&pairs, None, Some(vec!["BTC".to_string()]))), vec!["DAI-ETH-BTC"]);
+
+    // 40000 DAI 买 1 BTC, 买 20 ETH, 买回 42000 DAI, 每条腿 0.1% 手续费
+    let t0 = Ticker { idx: 2, ba: [40000.0, 1.0], bb: [39990.0, 1.0], ..Ticker::default() };
+    let t1 = Ticker { idx: 1, ba: [0.05, 100.0], bb: [0.0499, 100.0], ..Ticker::default() };
+    let t2 = Ticker { idx: 3, ba: [1.0 / 2100.0, 100000.0], bb: [1.0 / 2101.0, 100000.0], ..Ticker::default() };
+    let p = tp.calc_profit(&t0, &t1, &t2, false);
+    assert!(!p.rev);
+    assert_eq!(p.asset, "DAI");
+    assert_eq!(p.amount, 1.0);
+    let end = 0.999 / 0.05 * 0.999 * 2100.0 * 0.999;
+    assert!((p.profit - (end - 40000.0)).abs() < 0.01);
+
+    // 反向第一条腿卖出 1 ETH, 投入以 ETH 计
+    let (qty, spent, _) = tp.exec_legs(true, 1.0, [&t0, &t1, &t2]).unwrap();
+    assert_eq!((qty, spent), (1.0, 1.0));
+
+    let exec = simulate(tp, &p, [&pairs[1], &pairs[0], &pairs[2]]).unwrap();
+    assert_eq!(exec.asset, "DAI");
+    assert!((exec.pnl() - p.profit).abs() < 1e-9);
+
+    let books = [Depth::from_ticker(&t0), Depth::from_ticker(&t1), Depth::from_ticker(&t2)];
+    let dp = best_depth_profit(tp, [&books[0], &books[1], &books[2]]).unwrap();
+    assert!(!dp.rev);
+    assert_eq!(dp.amount, 1.0);
+    assert!((dp.profit - (end - 40000.0)).abs() < 0.01);
+  }
+}
@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod backtest_tests {
+  use std::io::Write;
+  use triangle::backtest::Backtest;
+  use triangle::config::Configuration;
+  use triangle::recorder::TickerReplay;
+  use triangle::trading_pair::round_step;
+
+  const RECORDS: &str = "P ETHBTC ETH BTC 0.001
+P ETHUSDT ETH USDT 0.001
+P BTCUSDT BTC USDT 0.00001
+T 1000 ETHBTC 0.0499 10 0.05 10
+T 1000 ETHUSDT 2000 10 2000.5 10
+T 1000 BTCUSDT 39999 5 40000 5
+T 2000 ETHUSDT 2100 10 2100.5 10
+T 3000 BTCUSDT 39999 5 40000 5
+T 5000 ETHUSDT 2000 10 2000.5 10
+";
+
+  #[test]
+  fn round_step_test() {
+    assert_eq!(round_step(1.23456, 0.01), 1.23);
+    assert!((round_step(3.0, 0.1) - 3.0).abs() < 1e-12);
+    assert_eq!(round_step(1.5, 0.0), 1.5);
+  }
+
+  #[test]
+  fn backtest_report_test() {
+    let path = std::env::temp_dir().join(format!("triangle-backtest-{}.log", std::process::id()));
+    std::fs::File::create(&path).unwrap().write_all(RECORDS.as_bytes()).unwrap();
+
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let mut replay = TickerReplay::open(&path, 0.0).unwrap();
+    let mut bt = Backtest::new(&cfg, replay.pairs(&cfg));
+    for (ts, symbol, tick) in replay.records() {
+      bt.on_record(ts, &symbol, tick);
+    }
+    let report = bt.finish();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(report.triangles.len(), 1);
+    let (name, st) = &report.triangles[0];
+    assert_eq!(name, "ETH-BTC-USDT");
+    assert_eq!(st.opportunities, 1);
+    assert_eq!(st.hits, 1);
+    assert_eq!(st.lifetime_ms, 3000);
+    assert_eq!(st.avg_lifetime_ms(), 3000.0);
+    // 按 investment_max 投入 0.0015 BTC 买 0.03 ETH, 卖出 0.029 ETH 得到 60.8391 USDT, 买回 0.00152 BTC 扣费后 0.00151848
+    assert!((st.pnl["BTC"] - 0.00001848).abs() < 1e-12);
+    assert_eq!(report.total.opportunities, 1);
+    assert_eq!(report.total.hit_rate(), 1.0);
+  }
+}
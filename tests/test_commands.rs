@@ -0,0 +1,90 @@
+mod common;
+
+#[cfg(test)]
+mod commands_tests {
+  use std::sync::mpsc;
+  use std::thread;
+  use std::time::Duration;
+  use log::LevelFilter;
+  use triangle::commands::{Command, DEFAULT_TOP};
+  use triangle::config::Configuration;
+  use triangle::tri_pair::Ticker;
+  use triangle::triangle::TriAngleArb;
+  use crate::common::{OpenSource, eth_pairs};
+
+  fn arb() -> TriAngleArb {
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let pairs = eth_pairs();
+    TriAngleArb::with_pairs(&cfg, pairs)
+  }
+
+  #[test]
+  fn parse_test() {
+    assert_eq!(Command::parse("/status"), Some(Command::Status));
+    assert_eq!(Command::parse("/status@triangle_bot"), Some(Command::Status));
+    assert_eq!(Command::parse("/top"), Some(Command::Top(DEFAULT_TOP)));
+    assert_eq!(Command::parse("/top 3"), Some(Command::Top(3)));
+    assert_eq!(Command::parse("/top 0"), Some(Command::Help));
+    assert_eq!(Command::parse("/PAUSE"), Some(Command::Pause));
+    assert_eq!(Command::parse(" /resume "), Some(Command::Resume));
+    assert_eq!(Command::parse("/threshold 0.25"), Some(Command::Threshold(0.25)));
+    assert_eq!(Command::parse("/threshold"), Some(Command::Help));
+    assert_eq!(Command::parse("/threshold abc"), Some(Command::Help));
+    assert_eq!(Command::parse("/loglevel debug"), Some(Command::LogLevel(LevelFilter::Debug)));
+    assert_eq!(Command::parse("/loglevel loud"), Some(Command::Help));
+    assert_eq!(Command::parse("/unknown"), Some(Command::Help));
+    assert_eq!(Command::parse("hello"), None);
+    assert_eq!(Command::parse(""), None);
+  }
+
+  #[test]
+  fn handle_command_test() {
+    let mut ta = arb();
+    assert!(ta.handle_command(&Command::Status).contains("triangles: 1"));
+    assert_eq!(ta.handle_command(&Command::Top(5)), "no profitable triangles");
+
+    ta.set_ticker(Ticker { idx: 1, ba: [0.05, 10.0], bb: [0.0499, 10.0], ..Ticker::default() });
+    ta.set_ticker(Ticker { idx: 2, ba: [2100.5, 10.0], bb: [2100.0, 10.0], ..Ticker::default() });
+    ta.set_ticker(Ticker { idx: 3, ba: [40000.0, 5.0], bb: [39999.0, 5.0], ..Ticker::default() });
+    let top = ta.top_profits(5);
+    assert_eq!(top.len(), 1);
+    assert!(top[0].profit > 0.0);
+    assert!(ta.handle_command(&Command::Top(5)).starts_with(&top[0].name));
+
+    ta.handle_command(&Command::Pause);
+    assert!(ta.is_paused());
+    assert!(ta.handle_command(&Command::Status).contains("trading: paused"));
+    ta.handle_command(&Command::Resume);
+    assert!(!ta.is_paused());
+
+    let old = ta.profit_threshold();
+    assert!(ta.handle_command(&Command::Threshold(-1.0)).starts_with("invalid"));
+    assert_eq!(ta.profit_threshold(), old);
+    // 阈值为 0 时所有机会都会交给执行器
+    assert!(ta.handle_command(&Command::Threshold(0.0)).starts_with("invalid"));
+    assert_eq!(ta.profit_threshold(), old);
+    ta.handle_command(&Command::Threshold(0.5));
+    assert_eq!(ta.profit_threshold(), 0.5);
+
+    ta.handle_command(&Command::LogLevel(LevelFilter::Debug));
+    assert_eq!(log::max_level(), LevelFilter::Debug);
+  }
+
+  #[test]
+  fn run_polls_commands_test() {
+    let mut ta = arb();
+    let (tx, rx) = mpsc::channel();
+    ta.set_commands(Some(rx));
+    tx.send(Command::Pause).unwrap();
+    tx.send(Command::Threshold(0.5)).unwrap();
+    let shutdown = ta.shutdown();
+    thread::spawn(move || {
+      thread::sleep(Duration::from_millis(300));
+      shutdown.trigger();
+    });
+    // 没有新的 ticker 时也处理命令
+    ta.start_with(&mut OpenSource { tx: None }).unwrap();
+    assert!(ta.is_paused());
+    assert_eq!(ta.profit_threshold(), 0.5);
+  }
+}
@@ -3,20 +3,122 @@ mod config_tests {
   #[test]
   fn attributes_test() {
     use triangle::config::*;
-    let cfg: Configuration = Configuration::new("config/sample_config.json");
+    let cfg: Configuration = Configuration::new("config/sample_config.json").unwrap();
     assert_eq!(cfg.api_key, "your-api-key-here");
     assert_eq!(cfg.api_secret, "your-api-secret-here");
     assert_eq!(cfg.investment_base, "BTC");
     assert_eq!(cfg.investment_min, 0.001);
     assert_eq!(cfg.investment_max, 0.0015);
     assert_eq!(cfg.investment_step, 0.0001);
-    assert_eq!(cfg.trading_enabled, false);
+    assert!(!cfg.trading_enabled);
     assert_eq!(cfg.trading_execution_cap, 1);
     assert_eq!(cfg.trading_taker_fee, 0.1);
     assert_eq!(cfg.trading_profit_threshold, 0.15);
-    assert_eq!(cfg.trading_age_threshold, 100);
+    assert_eq!(cfg.trading_age_threshold, 2000);
     assert_eq!(cfg.depth_size, 20);
+    assert_eq!(cfg.depth_enabled, None);
     assert_eq!(cfg.telegram_token, "your-telegram-bot-token");
     assert_eq!(cfg.telegram_user_id, 0);
   }
+
+  #[test]
+  fn validate_sample_test() {
+    use triangle::config::*;
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let v = cfg.validate();
+    assert!(v.is_ok(), "{}", v);
+    // 示例配置只有 telegram_token, 没有 user id
+    assert_eq!(v.warnings.len(), 1);
+  }
+
+  #[test]
+  fn validate_errors_test() {
+    use triangle::config::*;
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.investment_step = -0.0001;
+    cfg.investment_min = 0.002;
+    cfg.trading_taker_fee = 120.0;
+    cfg.fee = Some(1.5);
+    cfg.base_quotes = Some(vec![]);
+    cfg.depth_size = -1;
+    cfg.log_level = Some("loud".to_string());
+    let v = cfg.validate();
+    assert!(!v.is_ok());
+    assert_eq!(v.errors, vec![
+      ConfigIssue::NotPositive { field: "investment_step".to_string(), value: -0.0001 },
+      ConfigIssue::MinAboveMax { min: 0.002, max: 0.0015 },
+      ConfigIssue::OutOfRange { field: "trading_taker_fee".to_string(), value: 120.0, min: 0.0, max: 100.0 },
+      ConfigIssue::OutOfRange { field: "fee".to_string(), value: 1.5, min: 0.0, max: 1.0 },
+      ConfigIssue::Invalid { field: "depth_size".to_string(), reason: "-1 is negative".to_string() },
+      ConfigIssue::Missing { field: "base_quotes".to_string() },
+      ConfigIssue::Invalid { field: "log_level".to_string(), reason: "unknown log level: loud".to_string() },
+    ]);
+    assert!(v.to_string().contains("error: investment_min 0.002 is greater than investment_max 0.0015"));
+    assert!(v.to_string().ends_with("7 errors, 2 warnings"));
+  }
+
+  #[test]
+  fn validate_live_trading_test() {
+    use triangle::config::*;
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.trading_enabled = true;
+    cfg.paper_trading = Some(false);
+    cfg.api_secret = "".to_string();
+    cfg.fee_schedule = Some(FeeSchedule { vip_level: Some(2), vip_taker_fees: Some(vec![0.1, 0.09]), ..FeeSchedule::default() });
+    let v = cfg.validate();
+    assert_eq!(v.errors, vec![
+      ConfigIssue::Invalid { field: "fee_schedule.vip_level".to_string(), reason: "2 has no entry in vip_taker_fees (2 levels)".to_string() },
+      ConfigIssue::Missing { field: "api_secret".to_string() },
+    ]);
+    assert!(v.warnings.iter().any(|w| w.to_string() == "paper_trading: is false, real orders are placed"));
+  }
+
+  #[test]
+  fn validate_age_threshold_test() {
+    use triangle::config::*;
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.trading_age_threshold = STREAM_INTERVAL_MS;
+    let v = cfg.validate();
+    assert!(v.is_ok());
+    assert!(v.warnings.iter().any(|w| w.to_string() == "trading_age_threshold: is not above the 100ms stream interval, most triangles are skipped as stale"));
+  }
+
+  #[test]
+  fn validate_profit_threshold_test() {
+    use triangle::config::*;
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.trading_enabled = false;
+    cfg.trading_profit_threshold = 0.0;
+    assert_eq!(cfg.validate().errors, vec![
+      ConfigIssue::Invalid { field: "trading_profit_threshold".to_string(), reason: "is 0, must be positive".to_string() },
+    ]);
+  }
+
+  #[test]
+  fn validate_cycle_max_legs_test() {
+    use triangle::config::*;
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.cycle_max_legs = Some(2);
+    assert_eq!(cfg.validate().errors, vec![
+      ConfigIssue::Invalid { field: "cycle_max_legs".to_string(), reason: "is 2, cycles need at least 3 legs, cycle search would be off".to_string() },
+    ]);
+    cfg.cycle_max_legs = Some(6);
+    assert_eq!(cfg.validate().errors, vec![
+      ConfigIssue::OutOfRange { field: "cycle_max_legs".to_string(), value: 6.0, min: 3.0, max: 5.0 },
+    ]);
+    cfg.cycle_max_legs = Some(4);
+    assert!(cfg.validate().is_ok());
+  }
+
+  #[test]
+  fn validate_depth_test() {
+    use triangle::config::*;
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.depth_size = 0;
+    assert!(cfg.validate().is_ok());
+    cfg.depth_enabled = Some(true);
+    assert_eq!(cfg.validate().errors, vec![
+      ConfigIssue::Invalid { field: "depth_size".to_string(), reason: "is 0 while depth_enabled is true".to_string() },
+    ]);
+  }
 }
@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod config_layers_tests {
+  use std::path::PathBuf;
+  use triangle::config::{split_config_args, Configuration};
+
+  fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+  }
+
+  fn write(name: &str, text: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("triangle-{}-{}", std::process::id(), name));
+    std::fs::write(&path, text).unwrap();
+    path
+  }
+
+  #[test]
+  fn toml_matches_json_test() {
+    let json = Configuration::load(&["config/sample_config.json"], env(&[])).unwrap();
+    let toml = Configuration::load(&["config/sample_config.toml"], env(&[])).unwrap();
+    assert_eq!(toml.api_key, "");
+    assert_eq!(toml.investment_base, json.investment_base);
+    assert_eq!(toml.investment_min, json.investment_min);
+    assert_eq!(toml.trading_profit_threshold, json.trading_profit_threshold);
+    assert_eq!(toml.depth_size, json.depth_size);
+    assert_eq!(toml.base_quotes, json.base_quotes);
+    assert_eq!(toml.exclude_coins, json.exclude_coins);
+  }
+
+  #[test]
+  fn yaml_layer_test() {
+    let overlay = write("overlay.yaml", "
+trading_profit_threshold: 0.3
+base_quotes: [USDT]
+fee_schedule:
+  vip_level: 1
+  vip_taker_fees: [0.1, 0.09]
+");
+    let overlay2 = write("overlay2.yml", "
+fee_schedule:
+  bnb_discount: 25
+");
+    let cfg = Configuration::load(
+      &["config/sample_config.json", overlay.to_str().unwrap(), overlay2.to_str().unwrap()], env(&[])).unwrap();
+    // 后面的文件覆盖前面的, 对象逐字段合并
+    assert_eq!(cfg.trading_profit_threshold, 0.3);
+    assert_eq!(cfg.base_quotes, Some(vec!["USDT".to_string()]));
+    assert_eq!(cfg.investment_base, "BTC");
+    let schedule = cfg.fee_schedule.unwrap();
+    assert_eq!(schedule.vip_level, Some(1));
+    assert_eq!(schedule.bnb_discount, Some(25.0));
+
+    std::fs::remove_file(overlay).unwrap();
+    std::fs::remove_file(overlay2).unwrap();
+  }
+
+  #[test]
+  fn config_args_test() {
+    // 路径原样使用, 可以包含逗号
+    let overlay = write("over,lay.yaml", "trading_profit_threshold: 0.3\n");
+    let overlay = overlay.to_str().unwrap().to_string();
+    let args: Vec<String> = ["config/sample_config.json", "--config", &overlay, "replay", "tickers.log"]
+      .iter().map(|x| x.to_string()).collect();
+    let (paths, rest) = split_config_args(&args).unwrap();
+    assert_eq!(paths, vec!["config/sample_config.json".to_string(), overlay.clone()]);
+    assert_eq!(rest, vec!["replay".to_string(), "tickers.log".to_string()]);
+    let paths: Vec<&str> = paths.iter().map(|x| x.as_str()).collect();
+    let cfg = Configuration::from_paths(&paths).unwrap();
+    assert_eq!(cfg.trading_profit_threshold, 0.3);
+
+    assert!(split_config_args(&["config/sample_config.json".to_string(), "--config".to_string()]).is_err());
+    assert!(split_config_args(&[]).is_err());
+    std::fs::remove_file(overlay).unwrap();
+  }
+
+  #[test]
+  fn env_override_test() {
+    let cfg = Configuration::load(&["config/sample_config.toml"], env(&[
+      ("TRIANGLE_API_KEY", "12345"),
+      ("TRIANGLE_API_SECRET", "s3cret"),
+      ("TRIANGLE_TRADING_ENABLED", "true"),
+      ("TRIANGLE_TRADING_PROFIT_THRESHOLD", "0.25"),
+      ("TRIANGLE_BASE_QUOTES", "[\"BTC\",\"USDT\"]"),
+      ("TRIANGLE_METRICS_ADDR", "127.0.0.1:9898"),
+      ("TRIANGLE_TELEGRAM_USER_ID", "42"),
+      ("TRIANGLE_UNKNOWN", "1"),
+      ("PATH", "/usr/bin"),
+    ])).unwrap();
+    // 字符串字段不按 JSON 解析
+    assert_eq!(cfg.api_key, "12345");
+    assert_eq!(cfg.api_secret, "s3cret");
+    assert!(cfg.trading_enabled);
+    assert_eq!(cfg.trading_profit_threshold, 0.25);
+    assert_eq!(cfg.base_quotes, Some(vec!["BTC".to_string(), "USDT".to_string()]));
+    assert_eq!(cfg.metrics_addr, Some("127.0.0.1:9898".to_string()));
+    assert_eq!(cfg.telegram_user_id, 42);
+  }
+
+  #[test]
+  fn errors_test() {
+    let err = Configuration::load(&["config/sample_config.json"], env(&[("TRIANGLE_TRADING_ENABLED", "yes")]))
+      .unwrap_err().to_string();
+    assert!(err.contains("TRIANGLE_TRADING_ENABLED"), "{}", err);
+
+    let bad = write("bad.toml", "investment_base = ");
+    let err = Configuration::load(&[bad.to_str().unwrap()], env(&[])).unwrap_err().to_string();
+    assert!(err.contains("bad.toml"), "{}", err);
+    std::fs::remove_file(bad).unwrap();
+
+    assert!(Configuration::load(&["config/missing.yaml"], env(&[])).is_err());
+  }
+}
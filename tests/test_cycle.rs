@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod cycle_tests {
+  use triangle::config::Configuration;
+  use triangle::cycle::{spawn_discovery, CurrencyGraph, CycleIndex};
+  use triangle::trading_pair::TradingPair;
+  use triangle::tri_pair::{Side, Ticker};
+  use triangle::triangle::TriAngleArb;
+
+  // USDT -> BTC -> ETH -> XRP -> USDT 为 4 腿套利环, 没有三角套利
+  fn pairs() -> Vec<TradingPair> {
+    let pair = |idx: u32, base: &str, quote: &str| {
+      TradingPair::new(idx, format!("{}{}", base, quote), base.to_string(), quote.to_string(), 0.0001, 1.0)
+    };
+    vec![pair(1, "BTC", "USDT"), pair(2, "ETH", "BTC"), pair(3, "XRP", "ETH"), pair(4, "XRP", "USDT")]
+  }
+
+  // 以交易对 id 为下标
+  fn tickers() -> Vec<Ticker> {
+    let tick = |idx: u32, ask: f64, bid: f64| Ticker { idx, ba: [ask, 10.0], bb: [bid, 10.0], ..Ticker::default() };
+    vec![
+      Ticker::default(),
+      tick(1, 40000.0, 39999.0),
+      tick(2, 0.05, 0.0499),
+      tick(3, 0.00025, 0.000249),
+      tick(4, 0.52, 0.51),
+    ]
+  }
+
+  #[test]
+  fn find_cycles_test() {
+    let pairs = pairs();
+    let graph = CurrencyGraph::new(&pairs);
+    assert_eq!(graph.assets().len(), 4);
+    assert_eq!(graph.edges().len(), 8);
+    let tickers = tickers();
+
+    assert!(graph.find_cycles(&tickers, 3, 3).is_empty());
+    let cycles = graph.find_cycles(&tickers, 3, 5);
+    assert_eq!(cycles.len(), 1);
+    let c = &cycles[0];
+    assert_eq!(c.legs.len(), 4);
+    // 1 / 40000 * 20 * 4000 * 0.51 = 1.02
+    assert!((c.ratio(&tickers).unwrap() - 0.02).abs() < 1e-9);
+    let usdt = c.assets.iter().position(|a| a == "USDT").unwrap();
+    assert_eq!(c.legs[usdt], (1, Side::SideBuy));
+
+    // 环经过的每个交易对都索引到这个环
+    let mut index = CycleIndex::default();
+    assert_eq!(index.add(&cycles, 0), 1);
+    assert_eq!(index.len(), 1);
+    for pair in 1..=4 {
+      assert_eq!(index.get(pair).map(|v| v.len()), Some(1));
+    }
+    assert!(index.get(5).is_none());
+  }
+
+  #[test]
+  fn discover_cycles_test() {
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    let ta = TriAngleArb::with_pairs(&cfg, pairs());
+    // 未配置 cycle_max_legs 时不搜索
+    assert_eq!(ta.clone().discover_cycles(), 0);
+
+    cfg.cycle_max_legs = Some(5);
+    let mut ta2 = TriAngleArb::with_pairs(&cfg, ta.pairs());
+    for tick in tickers().into_iter().skip(1) {
+      ta2.set_ticker(tick);
+    }
+    assert_eq!(ta2.discover_cycles(), 1);
+    assert_eq!(ta2.discover_cycles(), 0);
+    let cycles = ta2.cycles(3).unwrap().clone();
+    let profits = ta2.on_ticker_cycles(&cycles);
+    assert_eq!(profits[0].legs, 4);
+    assert!((profits[0].ratio - 0.02).abs() < 1e-9);
+
+    // 价格变化后环不再盈利
+    ta2.set_ticker(Ticker { idx: 4, ba: [0.5, 10.0], bb: [0.49, 10.0], ..Ticker::default() });
+    assert!(ta2.on_ticker_cycles(&cycles)[0].ratio < 0.0);
+    assert!(ta.cycles(3).is_none());
+  }
+
+  #[test]
+  fn track_cycles_test() {
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.cycle_max_legs = Some(5);
+    let mut ta = TriAngleArb::with_pairs(&cfg, pairs());
+    for tick in tickers().into_iter().skip(1) {
+      ta.set_ticker(tick);
+    }
+    assert_eq!(ta.discover_cycles(), 1);
+    // 超过阈值时只在开始时输出一次
+    assert_eq!(ta.track_cycles(3, 0.01, 1000), 1);
+    assert_eq!(ta.track_cycles(3, 0.01, 1100), 0);
+    assert_eq!(ta.cycle_tracker().open_count(), 1);
+    // 低于阈值时结束
+    assert_eq!(ta.track_cycles(3, 0.03, 1200), 0);
+    assert_eq!(ta.cycle_tracker().open_count(), 0);
+  }
+
+  #[test]
+  fn cycle_index_expire_test() {
+    let graph = CurrencyGraph::new(&pairs());
+    let cycles = graph.find_cycles(&tickers(), 3, 5);
+    let mut index = CycleIndex::default();
+    assert_eq!(index.add(&cycles, 1000), 1);
+    // 再次发现时只刷新时间, 不重复加入
+    assert_eq!(index.add(&cycles, 2000), 0);
+    assert_eq!(index.len(), 1);
+    assert_eq!(index.get(3).unwrap().len(), 1);
+    assert_eq!(index.expire(2000), 0);
+    assert_eq!(index.expire(2001), 1);
+    assert!(index.is_empty());
+    assert!(index.get(3).is_none());
+  }
+
+  #[test]
+  fn spawn_discovery_test() {
+    let (snapshots, found) = spawn_discovery(CurrencyGraph::new(&pairs()), 5);
+    // 容量为 0, 线程空闲时 send 才返回
+    snapshots.send(tickers()).unwrap();
+    let cycles = found.recv().unwrap();
+    assert_eq!(cycles.len(), 1);
+    assert_eq!(cycles[0].legs.len(), 4);
+    drop(snapshots);
+    assert!(found.recv().is_err());
+  }
+}
@@ -0,0 +1,94 @@
+mod common;
+
+#[cfg(test)]
+mod depth_tests {
+  use std::collections::HashMap;
+  use std::sync::{Arc, RwLock};
+  use triangle::config::Configuration;
+  use triangle::depth::{calc_depth_profit, Depth};
+  use triangle::trading_pair::TradingPair;
+  use triangle::tri_pair::TriPair;
+  use triangle::triangle::TriAngleArb;
+  use crate::common::eth_pairs_with_fees;
+
+  fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+  }
+
+  fn pairs() -> Vec<TradingPair> {
+    eth_pairs_with_fees([1.0; 3])
+  }
+
+  fn books() -> [Depth; 3] {
+    [
+      Depth { idx: 1, asks: vec![[0.05, 2.0], [0.051, 2.0], [0.053, 10.0]], bids: vec![[0.0499, 10.0]] },
+      Depth { idx: 2, asks: vec![[2100.5, 10.0]], bids: vec![[2100.0, 3.0], [2060.0, 10.0]] },
+      Depth { idx: 3, asks: vec![[40000.0, 100.0]], bids: vec![[39999.0, 100.0]] },
+    ]
+  }
+
+  #[test]
+  fn depth_profit_test() {
+    let pairs = pairs();
+    let tp = TriPair::new("ETH".to_string(), vec![&pairs[0], &pairs[1], &pairs[2]]);
+    let b = books();
+
+    let dp = calc_depth_profit(&tp, false, [&b[0], &b[1], &b[2]]).unwrap();
+    // 每个 ETH 的边际收益: 0-2 为 0.0025, 2-3 为 0.0015, 3-4 为 0.0005, 4 以后为负
+    assert!(close(dp.amount, 4.0));
+    assert!(close(dp.cost, 0.202));
+    assert!(close(dp.profit, 0.007));
+    let amounts: Vec<f64> = dp.curve.iter().map(|x| x.amount).collect();
+    assert_eq!(amounts, vec![2.0, 3.0, 4.0, 13.0]);
+    let marginals = [0.0025, 0.0015, 0.0005, -0.0015];
+    for (point, m) in dp.curve.iter().zip(marginals.iter()) {
+      assert!(close(point.marginal, *m));
+    }
+
+    assert!(calc_depth_profit(&tp, true, [&b[0], &b[1], &b[2]]).is_none());
+  }
+
+  #[test]
+  fn depth_filters_test() {
+    let mut pairs = pairs();
+    // 第三条腿买入 0.209 BTC, 按 step 取整为 0.208, 零头的 USDT 不计入收益
+    pairs[2].filters.step = 0.002;
+    let tp = TriPair::new("ETH".to_string(), vec![&pairs[0], &pairs[1], &pairs[2]]);
+    let b = books();
+    let dp = calc_depth_profit(&tp, false, [&b[0], &b[1], &b[2]]).unwrap();
+    assert!(close(dp.amount, 4.0));
+    assert!(close(dp.profit, 0.006));
+
+    // 第三条腿的成交额低于 min_notional, 无法成交
+    pairs[2].filters.min_notional = 10000.0;
+    let tp = TriPair::new("ETH".to_string(), vec![&pairs[0], &pairs[1], &pairs[2]]);
+    assert!(calc_depth_profit(&tp, false, [&b[0], &b[1], &b[2]]).is_none());
+  }
+
+  #[test]
+  fn depth_book_test() {
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.investment_min = 0.01;
+    cfg.investment_max = 1.0;
+    cfg.investment_step = 0.001;
+    let mut ta = TriAngleArb::with_pairs(&cfg, pairs());
+    let b = books();
+    for d in b.iter() {
+      ta.set_ticker(d.ticker());
+    }
+    let angles = ta.angles(1).unwrap().clone();
+    // 只看最优一档时成交量受限于 2 ETH
+    let top = ta.on_ticker(&angles);
+    assert!(close(top.amount, 2.0));
+
+    let book: HashMap<u32, Depth> = b.iter().map(|d| (d.idx, d.clone())).collect();
+    ta.set_depth_book(Arc::new(RwLock::new(book)));
+    let profit = ta.on_ticker(&angles);
+    assert_eq!(profit.name, "ETH-BTC-USDT");
+    assert!(!profit.rev);
+    assert!(close(profit.amount, 4.0));
+    assert!(close(profit.profit, 0.007));
+    assert!(close(profit.ratio, 0.007 / 0.202));
+    assert!(close(profit.invest, 0.202));
+  }
+}
@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod error_tests {
+  use std::io::Write;
+  use std::time::Duration;
+  use binance::model::BookTickerEvent;
+  use triangle::config::Configuration;
+  use triangle::error::Error;
+  use triangle::market_data::Backoff;
+  use triangle::tri_pair::Ticker;
+
+  fn event(best_ask: &str) -> BookTickerEvent {
+    BookTickerEvent {
+      update_id: 1,
+      symbol: "ETHBTC".to_string(),
+      best_bid: "0.0499".to_string(),
+      best_bid_qty: "8".to_string(),
+      best_ask: best_ask.to_string(),
+      best_ask_qty: "10".to_string(),
+    }
+  }
+
+  #[test]
+  fn malformed_ticker_test() {
+    let tick = Ticker::from(1, &event("0.05")).unwrap();
+    assert_eq!(tick.ba, [0.05, 10.0]);
+    assert_eq!(tick.bb, [0.0499, 8.0]);
+
+    match Ticker::from(1, &event("")) {
+      Err(Error::Parse(e)) => assert!(e.contains("best ask")),
+      other => panic!("expected parse error, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn bad_config_test() {
+    assert!(matches!(Configuration::new("config/not_exists.json"), Err(Error::Config(_))));
+
+    let path = std::env::temp_dir().join("triangle_bad_config.json");
+    let mut file = std::fs::File::create(&path).unwrap();
+    writeln!(file, "{{\"api_key\": 1").unwrap();
+    assert!(matches!(Configuration::new(path.to_str().unwrap()), Err(Error::Config(_))));
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn backoff_test() {
+    let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+    let delays: Vec<u64> = (0..5).map(|_| backoff.next_delay().as_secs()).collect();
+    assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+    backoff.reset();
+    assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+  }
+}
@@ -0,0 +1,160 @@
+mod common;
+
+#[cfg(test)]
+mod export_tests {
+  use std::path::PathBuf;
+  use std::sync::{Arc, Mutex};
+  use triangle::config::Configuration;
+  use triangle::export::{ExportFormat, ExportSink};
+  use triangle::sink::OpportunitySink;
+  use triangle::tri_pair::{Profit, Ticker};
+  use triangle::triangle::TriAngleArb;
+  use crate::common::{VecSource, eth_pairs};
+
+  const HEADER: &str = "ts,name,rev,ratio,amount,profit,asset,value,invest,\
+    symbol1,ask1,ask_qty1,bid1,bid_qty1,update_id1,ticker_ts1,\
+    symbol2,ask2,ask_qty2,bid2,bid_qty2,update_id2,ticker_ts2,\
+    symbol3,ask3,ask_qty3,bid3,bid_qty3,update_id3,ticker_ts3";
+
+  const SYMBOLS: [&str; 3] = ["ETHBTC", "ETHUSDT", "BTCUSDT"];
+
+  fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("triangle-export-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+  }
+
+  fn profit(ts: u64) -> Profit {
+    Profit {
+      name: "ETH-BTC-USDT".to_string(),
+      ratio: 0.002,
+      amount: 1.5,
+      profit: 0.003,
+      asset: "ETH".to_string(),
+      value: 6.3,
+      ts,
+      tickers: [
+        Ticker { idx: 1, ba: [0.05, 10.0], bb: [0.0499, 8.0], update_id: 7, ts: 1 },
+        Ticker { idx: 2, ba: [2100.5, 3.0], bb: [2100.0, 4.0], update_id: 8, ts: 2 },
+        Ticker { idx: 3, ba: [40000.0, 1.0], bb: [39999.0, 2.0], update_id: 9, ts: 3 },
+      ],
+      ..Profit::default()
+    }
+  }
+
+  fn read(path: &PathBuf) -> Vec<String> {
+    std::fs::read_to_string(path).unwrap().lines().map(|x| x.to_string()).collect()
+  }
+
+  #[test]
+  fn csv_schema_test() {
+    let dir = dir("schema");
+    let mut sink = ExportSink::new(dir.to_str().unwrap(), ExportFormat::Csv, None, false).unwrap();
+    sink.record_profit(&profit(1_600_000_000_000), SYMBOLS).unwrap();
+    sink.flush().unwrap();
+
+    assert_eq!(sink.files().len(), 1);
+    assert!(sink.files()[0].ends_with("opportunities-20200913-122640.csv"));
+    let lines = read(&sink.files()[0]);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], HEADER);
+    assert_eq!(lines[1], "1600000000000,ETH-BTC-USDT,false,0.002,1.5,0.003,ETH,6.3,0.0,\
+      ETHBTC,0.05,10.0,0.0499,8.0,7,1,\
+      ETHUSDT,2100.5,3.0,2100.0,4.0,8,2,\
+      BTCUSDT,40000.0,1.0,39999.0,2.0,9,3");
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn scan_exports_every_profit_test() {
+    let dir = dir("scan");
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let sink = Arc::new(Mutex::new(ExportSink::new(dir.to_str().unwrap(), ExportFormat::Csv, None, false).unwrap()));
+    let mut ta = TriAngleArb::with_pairs(&cfg, eth_pairs());
+    ta.add_sink(sink.clone());
+    ta.start_with(&mut VecSource {
+      tickers: vec![
+        ("ETHBTC", [0.05, 10.0], [0.0499, 10.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("BTCUSDT", [40000.0, 5.0], [39999.0, 5.0]),
+        // 同一个机会持续, 每次超过阈值的计算都导出一行
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("ETHUSDT", [2101.0, 10.0], [2100.5, 10.0]),
+      ],
+    }).unwrap();
+
+    let mut sink = sink.lock().unwrap();
+    sink.flush().unwrap();
+    let lines = read(&sink.files()[0]);
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].contains(",ETHUSDT,2100.5,10.0,2100.0,10.0,"));
+    assert!(lines[2].contains(",ETHUSDT,2101.0,10.0,2100.5,10.0,"));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn rotate_hourly_test() {
+    let dir = dir("hourly");
+    let mut sink = ExportSink::new(dir.to_str().unwrap(), ExportFormat::Csv, None, true).unwrap();
+    let hour = 3_600_000;
+    let start = 1_600_000_000_000 / hour * hour;
+    sink.record_profit(&profit(start), SYMBOLS).unwrap();
+    sink.record_profit(&profit(start + hour - 1), SYMBOLS).unwrap();
+    sink.record_profit(&profit(start + hour), SYMBOLS).unwrap();
+    sink.flush().unwrap();
+
+    assert_eq!(sink.files().len(), 2);
+    assert_eq!(read(&sink.files()[0]).len(), 3);
+    let second = read(&sink.files()[1]);
+    assert_eq!(second.len(), 2);
+    assert_eq!(second[0], HEADER);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn rotate_by_size_test() {
+    let dir = dir("size");
+    // csv 的缓冲写满 (8KB) 后才计入文件大小, 每行约 200 字节
+    let mut sink = ExportSink::new(dir.to_str().unwrap(), ExportFormat::Csv, Some(1), false).unwrap();
+    for i in 0..100 {
+      sink.record_profit(&profit(1_600_000_000_000 + i), SYMBOLS).unwrap();
+    }
+    sink.flush().unwrap();
+
+    let files = sink.files().to_vec();
+    assert!(files.len() >= 2);
+    // 同一秒内轮换的文件名带序号, 不覆盖
+    assert!(files[1].to_str().unwrap().ends_with("opportunities-20200913-122640-1.csv"));
+    let rows: usize = files.iter().map(|f| read(f).len() - 1).sum();
+    assert_eq!(rows, 100);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn parse_format_test() {
+    assert_eq!(ExportFormat::parse("CSV").unwrap(), ExportFormat::Csv);
+    assert!(ExportFormat::parse("xlsx").is_err());
+    assert_eq!(ExportFormat::parse("parquet").is_ok(), cfg!(feature = "parquet"));
+  }
+
+  #[cfg(feature = "parquet")]
+  #[test]
+  fn parquet_test() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let dir = dir("parquet");
+    let mut sink = ExportSink::new(dir.to_str().unwrap(), ExportFormat::Parquet, None, false).unwrap();
+    sink.record_profit(&profit(1_600_000_000_000), SYMBOLS).unwrap();
+    sink.record_profit(&profit(1_600_000_000_001), SYMBOLS).unwrap();
+    // Parquet 在 flush 时写完文件
+    sink.flush().unwrap();
+
+    let file = std::fs::File::open(&sink.files()[0]).unwrap();
+    let reader = SerializedFileReader::new(file).unwrap();
+    let meta = reader.metadata();
+    assert_eq!(meta.file_metadata().num_rows(), 2);
+    assert_eq!(meta.file_metadata().schema_descr().num_columns(), 30);
+    assert_eq!(meta.file_metadata().schema_descr().column(0).name(), "ts");
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,56 @@
+mod common;
+
+#[cfg(test)]
+mod fee_tests {
+  use std::collections::HashMap;
+  use triangle::config::{Configuration, FeeSchedule};
+  use triangle::tri_pair::{Ticker, TriPair};
+  use crate::common::eth_pairs_with_fees;
+
+  fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-12
+  }
+
+  #[test]
+  fn pair_fee_test() {
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    // trading_taker_fee 0.1%
+    assert!(close(cfg.pair_fee("ETHBTC"), 0.999));
+
+    let symbols: HashMap<String, f64> = vec![("BTCUSDT".to_string(), 0.0)].into_iter().collect();
+    cfg.fee_schedule = Some(FeeSchedule {
+      vip_level: Some(1),
+      vip_taker_fees: Some(vec![0.1, 0.09, 0.08]),
+      bnb_discount: Some(25.0),
+      symbols: Some(symbols),
+    });
+    // VIP1 0.09% 使用 BNB 抵扣后为 0.0675%
+    assert!(close(cfg.pair_fee("ETHBTC"), 1.0 - 0.000675));
+    assert!(close(cfg.pair_fee("BTCUSDT"), 1.0));
+
+    cfg.fee_schedule = Some(FeeSchedule { bnb_discount: Some(25.0), ..FeeSchedule::default() });
+    assert!(close(cfg.pair_fee("ETHBTC"), 1.0 - 0.00075));
+  }
+
+  #[test]
+  fn calc_profit_per_pair_fee_test() {
+    let tickers = [
+      Ticker { idx: 1, ba: [0.05, 10.0], bb: [0.0499, 10.0], ..Ticker::default() },
+      Ticker { idx: 2, ba: [2100.5, 10.0], bb: [2100.0, 10.0], ..Ticker::default() },
+      Ticker { idx: 3, ba: [40000.0, 5.0], bb: [39999.0, 5.0], ..Ticker::default() },
+    ];
+    let profit_with = |bridge_fee: f64| {
+      let pairs = eth_pairs_with_fees([0.999, 0.999, bridge_fee]);
+      let tp = TriPair::new("ETH".to_string(), vec![&pairs[0], &pairs[1], &pairs[2]]);
+      assert_eq!(tp.fees, [0.999, 0.999, bridge_fee]);
+      tp.calc_profit(&tickers[0], &tickers[1], &tickers[2], false)
+    };
+
+    let p1 = profit_with(0.999);
+    let p2 = profit_with(1.0);
+    // 0.5 BTC 买 10 ETH, 扣费后卖出 9.99 ETH 得到 20958.021 USDT, 按 step 买回 0.52395 BTC
+    assert!(close(p1.profit, 0.52395 * 0.999 - 0.5));
+    assert!(close(p2.profit, 0.52395 - 0.5));
+    assert!(p2.profit > p1.profit);
+  }
+}
@@ -0,0 +1,60 @@
+mod common;
+
+#[cfg(test)]
+mod filters_tests {
+  use triangle::trading_pair::PairFilters;
+  use triangle::tri_pair::{Ticker, TriPair};
+  use crate::common::eth_pairs;
+
+  fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-12
+  }
+
+  fn tickers(qty: f64) -> [Ticker; 3] {
+    [
+      Ticker { idx: 1, ba: [0.05, qty], bb: [0.0499, qty], ..Ticker::default() },
+      Ticker { idx: 2, ba: [2100.5, qty], bb: [2100.0, qty], ..Ticker::default() },
+      Ticker { idx: 3, ba: [40000.0, 5.0], bb: [39999.0, 5.0], ..Ticker::default() },
+    ]
+  }
+
+  fn tri_pair(eth_btc: PairFilters) -> TriPair {
+    let mut pairs = eth_pairs();
+    pairs[0] = pairs[0].clone().with_filters(eth_btc);
+    TriPair::new("ETH".to_string(), vec![&pairs[0], &pairs[1], &pairs[2]])
+  }
+
+  #[test]
+  fn lot_size_test() {
+    let tp = tri_pair(PairFilters { step: 0.1, max_qty: 5.0, ..PairFilters::default() });
+    let t = tickers(10.0);
+    let p = tp.calc_profit(&t[0], &t[1], &t[2], false);
+    // 买入数量被 max_qty 限制为 5 ETH, 扣费后卖出 4.995 ETH 得到 10479.0105 USDT, 买回 0.26197 BTC
+    assert!(!p.rev);
+    assert!(close(p.amount, 5.0));
+    assert!(close(p.profit, 0.26197 * 0.999 - 0.25));
+    assert!(close(p.ratio, p.profit / 0.25));
+
+    let t = tickers(1.2345);
+    let p = tp.calc_profit(&t[0], &t[1], &t[2], false);
+    assert!(close(p.amount, 1.2));
+  }
+
+  #[test]
+  fn min_notional_test() {
+    let t = tickers(0.1);
+    // 0.1 ETH 约 0.005 BTC, 不足 0.01 BTC 的最小成交额, 两个方向都无法成交
+    let tp = tri_pair(PairFilters { step: 0.001, min_notional: 0.01, ..PairFilters::default() });
+    let p = tp.calc_profit(&t[0], &t[1], &t[2], false);
+    assert_eq!(p.profit, 0.0);
+    assert_eq!(p.name, tp.name);
+
+    let tp = tri_pair(PairFilters { step: 0.001, min_qty: 0.5, ..PairFilters::default() });
+    let p = tp.calc_profit(&t[0], &t[1], &t[2], false);
+    assert_eq!(p.profit, 0.0);
+
+    let tp = tri_pair(PairFilters { step: 0.001, min_notional: 0.001, ..PairFilters::default() });
+    let p = tp.calc_profit(&t[0], &t[1], &t[2], false);
+    assert!(p.profit > 0.0);
+  }
+}
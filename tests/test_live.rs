@@ -0,0 +1,150 @@
+mod common;
+
+#[cfg(test)]
+mod live_tests {
+  use std::collections::HashMap;
+  use std::io::{BufRead, BufReader, Read, Write};
+  use std::net::{TcpListener, TcpStream};
+  use std::sync::{Arc, Mutex};
+  use std::thread;
+  use std::time::Duration;
+  use crate::common::eth_opportunity;
+
+  use binance::config::Config;
+  use triangle::config::Configuration;
+  use triangle::executor::{Executor, Outcome};
+  use triangle::live::LiveExecutor;
+
+  // 本地 mock binance REST 服务, 按固定价格成交市价单, 手续费 0.1% 以得到的资产收取
+  fn fill_price(symbol: &str) -> f64 {
+    match symbol {
+      "ETHBTC" => 0.0501,
+      "ETHUSDT" => 2100.0,
+      "BTCUSDT" => 40000.0,
+      _ => 0.0,
+    }
+  }
+
+  fn order_response(params: &HashMap<String, String>) -> String {
+    let symbol = params["symbol"].as_str();
+    let side = params["side"].as_str();
+    let price = fill_price(symbol);
+    let qty: f64 = match params.get("quantity") {
+      Some(q) => q.parse().unwrap(),
+      None => (params["quoteOrderQty"].parse::<f64>().unwrap() / price * 1e5).floor() / 1e5,
+    };
+    let quote = qty * price;
+    let (commission, asset) = if side == "BUY" {
+      (qty * 0.001, symbol.trim_end_matches("BTC").trim_end_matches("USDT"))
+    } else {
+      (quote * 0.001, if symbol.ends_with("BTC") { "BTC" } else { "USDT" })
+    };
+    format!(
+      r#"{{"symbol":"{}","orderId":1,"orderListId":-1,"clientOrderId":"x","transactTime":1,"price":"0","origQty":"{}","executedQty":"{}","cummulativeQuoteQty":"{}","status":"FILLED","timeInForce":"GTC","type":"MARKET","side":"{}","fills":[{{"price":"{}","qty":"{}","commission":"{}","commissionAsset":"{}","tradeId":1}}]}}"#,
+      symbol, qty, qty, quote, side, price, qty, commission, asset
+    )
+  }
+
+  const ACCOUNT: &str = r#"{"makerCommission":10,"takerCommission":10,"buyerCommission":0,"sellerCommission":0,"canTrade":true,"canWithdraw":true,"canDeposit":true,"balances":[{"asset":"BTC","free":"0.01","locked":"0"},{"asset":"ETH","free":"0.1","locked":"0"},{"asset":"USDT","free":"100","locked":"0"},{"asset":"BNB","free":"1","locked":"0"}]}"#;
+
+  fn handle(stream: TcpStream, delay: u64, orders: Arc<Mutex<Vec<String>>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+    loop {
+      let mut line = String::new();
+      if reader.read_line(&mut line).unwrap_or(0) == 0 {
+        return
+      }
+      let target = line.split_whitespace().nth(1).unwrap_or("").to_string();
+      let mut content_length = 0;
+      loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).unwrap();
+        if header == "\r\n" {
+          break
+        }
+        if header.to_lowercase().starts_with("content-length:") {
+          content_length = header[15..].trim().parse().unwrap();
+        }
+      }
+      let mut body = vec![0u8; content_length];
+      reader.read_exact(&mut body).unwrap();
+
+      let (path, query) = target.split_at(target.find('?').unwrap_or(target.len()));
+      let params: HashMap<String, String> = query.trim_start_matches('?').split('&')
+        .filter_map(|kv| {
+          let mut it = kv.splitn(2, '=');
+          Some((it.next()?.to_string(), it.next()?.to_string()))
+        })
+        .collect();
+      let response = if path == "/api/v3/order" {
+        thread::sleep(Duration::from_millis(delay));
+        orders.lock().unwrap().push(params["symbol"].clone());
+        order_response(&params)
+      } else {
+        ACCOUNT.to_string()
+      };
+      write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        response.len(), response).unwrap();
+    }
+  }
+
+  fn start_mock(delay: u64) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let orders = Arc::new(Mutex::new(Vec::new()));
+    let thread_orders = orders.clone();
+    thread::spawn(move || {
+      for stream in listener.incoming() {
+        let orders = thread_orders.clone();
+        thread::spawn(move || handle(stream.unwrap(), delay, orders));
+      }
+    });
+    (endpoint, orders)
+  }
+
+  #[test]
+  fn execute_blocking_test() {
+    let (endpoint, orders) = start_mock(0);
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let ex = LiveExecutor::with_config(&cfg, &Config::default().set_rest_api_endpoint(endpoint));
+    let (pairs, tp, profit) = eth_opportunity();
+
+    let report = ex.execute_blocking(&tp, &profit, [&pairs[0], &pairs[1], &pairs[2]]).unwrap();
+    assert_eq!(*orders.lock().unwrap(), vec!["ETHBTC", "ETHUSDT", "BTCUSDT"]);
+    assert_eq!(report.symbols, vec!["ETHBTC", "ETHUSDT", "BTCUSDT"]);
+    // 0.03 ETH @ 0.0501, 卖出 0.029 ETH @ 2100, 用 60.8391 USDT 买入 0.00152 BTC
+    assert_eq!(report.exec.asset, "BTC");
+    assert!((report.exec.start - 0.001503).abs() < 1e-12);
+    assert!((report.exec.fills[1].qty - 0.029).abs() < 1e-12);
+    assert!((report.exec.end - 0.00151848).abs() < 1e-12);
+    assert!((report.slippage[0] - 0.002).abs() < 1e-9);
+    assert!(report.slippage[1].abs() < 1e-9);
+    assert!(report.slippage[2].abs() < 1e-9);
+    assert_eq!(report.balances.len(), 3);
+    assert_eq!(report.balances["USDT"], 100.0);
+  }
+
+  #[test]
+  fn execution_cap_test() {
+    let (endpoint, orders) = start_mock(200);
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    assert_eq!(cfg.trading_execution_cap, 1);
+    let mut ex = LiveExecutor::with_config(&cfg, &Config::default().set_rest_api_endpoint(endpoint));
+    let reports = ex.reports().unwrap();
+    // 只能取一次
+    assert!(ex.reports().is_none());
+    let (pairs, tp, profit) = eth_opportunity();
+
+    assert!(matches!(ex.execute(&tp, &profit, [&pairs[0], &pairs[1], &pairs[2]]), Outcome::Submitted));
+    assert_eq!(ex.inflight(), 1);
+    // 超过 trading_execution_cap, 跳过
+    assert!(matches!(ex.execute(&tp, &profit, [&pairs[0], &pairs[1], &pairs[2]]), Outcome::Skipped));
+
+    let report = reports.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(report.name, tp.name);
+    assert!(reports.recv_timeout(Duration::from_millis(500)).is_err());
+    assert_eq!(orders.lock().unwrap().len(), 3);
+    assert_eq!(ex.inflight(), 0);
+  }
+}
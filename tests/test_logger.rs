@@ -0,0 +1,52 @@
+#[cfg(test)]
+mod logger_tests {
+  use chrono::{TimeZone, Utc};
+  use log::{Level, LevelFilter, Record};
+  use triangle::logger::{format_record, parse_level, LogFormat};
+
+  fn format(format_: LogFormat, kvs: &[(&str, log::kv::Value)]) -> String {
+    let now = Utc.timestamp_millis_opt(1_600_000_000_123).unwrap();
+    let kvs: Vec<(&str, log::kv::Value)> = kvs.to_vec();
+    let name = "ETH-BTC-USDT";
+    format_record(&Record::builder()
+      .level(Level::Info)
+      .target("opportunity")
+      .args(format_args!("opportunity {} opened", name))
+      .key_values(&kvs)
+      .build(), format_, now)
+  }
+
+  #[test]
+  fn json_test() {
+    let line = format(LogFormat::Json, &[
+      ("triangle", "ETH-BTC-USDT".into()),
+      ("rev", true.into()),
+      ("ratio", 0.0025.into()),
+      ("ticks", 3u64.into()),
+      ("pnl", (-2i64).into()),
+      ("bad", f64::NAN.into()),
+    ]);
+    assert_eq!(line, "{\"ts\":\"2020-09-13T12:26:40.123Z\",\"level\":\"INFO\",\"target\":\"opportunity\",\
+      \"msg\":\"opportunity ETH-BTC-USDT opened\",\"triangle\":\"ETH-BTC-USDT\",\"rev\":true,\"ratio\":0.0025,\
+      \"ticks\":3,\"pnl\":-2,\"bad\":\"NaN\"}");
+    let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(v["ratio"], 0.0025);
+  }
+
+  #[test]
+  fn text_test() {
+    let line = format(LogFormat::Text, &[("ratio", 0.0025.into()), ("error", "connection reset".into())]);
+    assert_eq!(line, "2020-09-13T12:26:40.123Z INFO  opportunity: opportunity ETH-BTC-USDT opened \
+      ratio=0.0025 error=\"connection reset\"");
+  }
+
+  #[test]
+  fn parse_test() {
+    assert_eq!(LogFormat::parse("JSON").unwrap(), LogFormat::Json);
+    assert_eq!(LogFormat::parse("text").unwrap(), LogFormat::Text);
+    assert!(LogFormat::parse("xml").is_err());
+    assert_eq!(parse_level("debug").unwrap(), LevelFilter::Debug);
+    assert_eq!(parse_level("OFF").unwrap(), LevelFilter::Off);
+    assert!(parse_level("verbose").is_err());
+  }
+}
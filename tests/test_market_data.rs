@@ -0,0 +1,49 @@
+mod common;
+
+#[cfg(test)]
+mod market_data_tests {
+  use std::thread;
+  use std::time::{Duration, Instant};
+  use triangle::config::Configuration;
+  use triangle::triangle::TriAngleArb;
+  use crate::common::{OpenSource, VecSource, eth_pairs};
+
+  #[test]
+  fn start_with_custom_source_test() {
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let mut ta = TriAngleArb::with_pairs(&cfg, eth_pairs());
+    let mut source = VecSource {
+      tickers: vec![
+        ("ETHBTC", [0.05, 10.0], [0.0499, 8.0]),
+        ("ETHUSDT", [2000.0, 3.0], [1999.0, 4.0]),
+        ("BTCUSDT", [40000.0, 1.0], [39999.0, 2.0]),
+        ("ETHUSDT", [2001.0, 5.0], [2000.0, 6.0]),
+      ],
+    };
+    // 数据源结束后 start_with 返回
+    ta.start_with(&mut source).unwrap();
+
+    let eth_usdt = ta.symbol_idx("ETH/USDT").unwrap();
+    let tick = ta.ticker(eth_usdt).unwrap();
+    assert_eq!(tick.ba, [2001.0, 5.0]);
+    assert_eq!(tick.bb, [2000.0, 6.0]);
+    let btc_usdt = ta.symbol_idx("BTC/USDT").unwrap();
+    assert_eq!(ta.ticker(btc_usdt).unwrap().ba, [40000.0, 1.0]);
+  }
+
+  #[test]
+  fn shutdown_test() {
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let mut ta = TriAngleArb::with_pairs(&cfg, eth_pairs());
+    let shutdown = ta.shutdown();
+    let start = Instant::now();
+    thread::spawn(move || {
+      thread::sleep(Duration::from_millis(300));
+      shutdown.trigger();
+    });
+    // 队列没有断开, 退出标志置位后 start_with 返回
+    ta.start_with(&mut OpenSource { tx: None }).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert!(start.elapsed() < Duration::from_secs(5));
+  }
+}
@@ -0,0 +1,73 @@
+mod common;
+
+#[cfg(test)]
+mod metrics_tests {
+  use std::io::{Read, Write};
+  use std::net::{SocketAddr, TcpStream};
+  use triangle::config::Configuration;
+  use triangle::metrics::{self, Metrics};
+  use triangle::triangle::TriAngleArb;
+  use crate::common::{VecSource, eth_pairs};
+
+  fn get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+  }
+
+  #[test]
+  fn render_test() {
+    let m = Metrics::new();
+    m.ticker_received("ETHBTC");
+    m.ticker_received("ETHBTC");
+    m.ws_reconnected();
+    m.observe_latency(0.003);
+    m.observe_latency(2.0);
+    m.triangle_evaluated("ETH-BTC-USDT", 0.002, true);
+    m.triangle_evaluated("ETH-BTC-USDT", -0.001, false);
+
+    let text = m.render();
+    assert!(text.contains("triangle_tickers_received_total{symbol=\"ETHBTC\"} 2\n"));
+    assert!(text.contains("triangle_ws_reconnects_total 1\n"));
+    assert!(text.contains("triangle_tick_latency_seconds_bucket{le=\"0.0025\"} 0\n"));
+    assert!(text.contains("triangle_tick_latency_seconds_bucket{le=\"0.005\"} 1\n"));
+    assert!(text.contains("triangle_tick_latency_seconds_bucket{le=\"1\"} 1\n"));
+    assert!(text.contains("triangle_tick_latency_seconds_bucket{le=\"+Inf\"} 2\n"));
+    assert!(text.contains("triangle_tick_latency_seconds_count 2\n"));
+    assert!(text.contains("triangle_triangles_evaluated_total 2\n"));
+    assert!(text.contains("triangle_opportunities_total 1\n"));
+    assert!(text.contains("triangle_ratio{triangle=\"ETH-BTC-USDT\"} -0.001\n"));
+  }
+
+  #[test]
+  fn serve_scan_metrics_test() {
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let pairs = eth_pairs();
+    let mut ta = TriAngleArb::with_pairs(&cfg, pairs);
+    let m = Metrics::new();
+    ta.set_metrics(Some(m.clone()));
+    let addr = metrics::serve("127.0.0.1:0", m.clone()).unwrap();
+
+    ta.start_with(&mut VecSource {
+      tickers: vec![
+        ("ETHBTC", [0.05, 10.0], [0.0499, 10.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("BTCUSDT", [40000.0, 5.0], [39999.0, 5.0]),
+        ("ETHUSDT", [2101.0, 10.0], [2100.5, 10.0]),
+      ],
+    }).unwrap();
+    // 前三个 ticker 用于初始化, 扫描循环只计算最后一个
+    assert_eq!(m.evaluated(), 1);
+
+    let response = get(addr, "/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("triangle_tickers_received_total{symbol=\"ETHUSDT\"} 1\n"));
+    assert!(response.contains("triangle_triangles_evaluated_total 1\n"));
+    assert!(response.contains("triangle_tick_latency_seconds_count 1\n"));
+    assert!(response.contains("triangle_ratio{triangle="));
+
+    assert!(get(addr, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+  }
+}
@@ -0,0 +1,138 @@
+mod common;
+
+#[cfg(test)]
+mod notifier_tests {
+  use std::io::{BufRead, BufReader, Read, Write};
+  use std::net::{TcpListener, TcpStream};
+  use std::sync::{Arc, Mutex, OnceLock};
+  use std::thread;
+  use std::time::{Duration, Instant};
+  use triangle::config::Configuration;
+  use triangle::notifier::Notifier;
+  use triangle::triangle::TriAngleArb;
+  use crate::common::{VecSource, eth_pairs};
+
+  const RESPONSE: &str = r#"{"ok":true,"result":{"message_id":1,"date":0,"from":{"id":1,"is_bot":true,"first_name":"bot"},"chat":{"id":42,"type":"private","first_name":"user"},"text":"ok"}}"#;
+
+  type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+  // 读取一个 http 请求, 返回 (路径, body), 连接关闭时返回 None
+  fn read_request(reader: &mut BufReader<TcpStream>) -> Option<(String, String)> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+      return None
+    }
+    let path = line.split_whitespace().nth(1)?.to_string();
+    let mut len = 0;
+    loop {
+      let mut header = String::new();
+      reader.read_line(&mut header).ok()?;
+      let header = header.trim_end();
+      if header.is_empty() {
+        break
+      }
+      if let Some(v) = header.to_lowercase().strip_prefix("content-length:") {
+        len = v.trim().parse().ok()?;
+      }
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).ok()?;
+    Some((path, String::from_utf8(body).ok()?))
+  }
+
+  // 本地 Bot API 服务, 记录收到的请求并返回 sendMessage 的成功响应
+  // TELEGRAM_API_URL 是进程级的, 所有测试共用一个服务, 以 token 区分请求
+  fn mock_server() -> Requests {
+    static SERVER: OnceLock<Requests> = OnceLock::new();
+    SERVER.get_or_init(|| {
+      let (url, requests) = spawn_server();
+      std::env::set_var("TELEGRAM_API_URL", &url);
+      requests
+    }).clone()
+  }
+
+  // path 为 /bot<token>/sendMessage 的请求 body, 等待至少 n 个
+  fn wait_requests(requests: &Requests, token: &str, n: usize) -> Vec<String> {
+    let path = format!("/bot{}/sendMessage", token);
+    let bodies = || -> Vec<String> {
+      requests.lock().unwrap().iter().filter(|(p, _)| *p == path).map(|(_, body)| body.clone()).collect()
+    };
+    let start = Instant::now();
+    while bodies().len() < n && start.elapsed() < Duration::from_secs(10) {
+      thread::sleep(Duration::from_millis(20));
+    }
+    thread::sleep(Duration::from_millis(100));
+    bodies()
+  }
+
+  fn spawn_server() -> (String, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let requests: Requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+    thread::spawn(move || {
+      for stream in listener.incoming() {
+        let stream = stream.unwrap();
+        let recorded = recorded.clone();
+        thread::spawn(move || {
+          let mut writer = stream.try_clone().unwrap();
+          let mut reader = BufReader::new(stream);
+          while let Some(req) = read_request(&mut reader) {
+            recorded.lock().unwrap().push(req);
+            let _ = write!(writer, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+              RESPONSE.len(), RESPONSE);
+          }
+        });
+      }
+    });
+    (url, requests)
+  }
+
+  #[test]
+  fn disabled_without_user_test() {
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    assert!(Notifier::from_config(&cfg).is_none());
+  }
+
+  #[test]
+  fn send_with_rate_limit_test() {
+    let requests = mock_server();
+    let notifier = Notifier::spawn("123:token", 42, 2, 0.001);
+    assert!(notifier.notify("opportunity ETH-BTC-USDT"));
+    assert!(notifier.notify("executed ETH-BTC-USDT"));
+    // 每分钟最多 2 条
+    assert!(!notifier.notify("dropped"));
+    assert!(!notifier.clone().notify("dropped"));
+    assert_eq!(notifier.suppressed(), 2);
+
+    let requests = wait_requests(&requests, "123:token", 2);
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0].contains("42"));
+    assert!(requests[0].contains("opportunity ETH-BTC-USDT"));
+    assert!(requests[1].contains("executed ETH-BTC-USDT"));
+  }
+
+  #[test]
+  fn notify_when_ratio_crosses_test() {
+    let requests = mock_server();
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let mut ta = TriAngleArb::with_pairs(&cfg, eth_pairs());
+    ta.set_notifier(Some(Notifier::spawn("456:token", 42, 20, 0.03)));
+    ta.start_with(&mut VecSource {
+      tickers: vec![
+        ("ETHBTC", [0.05, 10.0], [0.0499, 10.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("BTCUSDT", [40000.0, 5.0], [39999.0, 5.0]),
+        // 机会开始时约 1.3%, 低于通知阈值
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        // 持续期间超过阈值时通知一次
+        ("ETHUSDT", [2200.5, 10.0], [2200.0, 10.0]),
+        ("ETHUSDT", [2201.5, 10.0], [2201.0, 10.0]),
+      ],
+    }).unwrap();
+
+    let requests = wait_requests(&requests, "456:token", 1);
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].contains("opportunity ETH-BTC-USDT rev=false: ratio 5.8940%"));
+  }
+}
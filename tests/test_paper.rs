@@ -0,0 +1,114 @@
+mod common;
+
+#[cfg(test)]
+mod paper_tests {
+  use std::collections::HashMap;
+  use triangle::config::Configuration;
+  use triangle::executor::{Executor, Outcome};
+  use triangle::paper::PaperExecutor;
+  use triangle::trading_pair::TradingPair;
+  use triangle::tri_pair::{Profit, TriPair};
+  use triangle::triangle::TriAngleArb;
+  use crate::common::{VecSource, eth_opportunity, eth_pairs};
+
+  #[test]
+  fn execute_updates_ledger_test() {
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let (pairs, tp, profit) = eth_opportunity();
+    assert!(!profit.rev);
+
+    let mut ex = PaperExecutor::new(&cfg);
+    assert_eq!(ex.balance("BTC"), 0.0015);
+    let Outcome::Executed(exec) = ex.execute(&tp, &profit, [&pairs[0], &pairs[1], &pairs[2]]) else { panic!("not executed") };
+
+    // 投入 investment_max 0.0015 BTC 买 0.03 ETH, 卖出 0.029 ETH 得到 60.8391 USDT, 买回 0.00152 BTC
+    assert_eq!(exec.asset, "BTC");
+    assert_eq!(exec.fills.len(), 3);
+    assert!((exec.start - 0.0015).abs() < 1e-12);
+    assert!((ex.balance("BTC") - 0.00151848).abs() < 1e-12);
+    assert!((ex.balance("ETH") - 0.00097).abs() < 1e-12);
+    assert!((ex.balance("USDT") - 0.0391).abs() < 1e-9);
+    assert_eq!(ex.executions(), 1);
+  }
+
+  #[test]
+  fn skip_below_investment_min_test() {
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    let balances: HashMap<String, f64> = vec![("BTC".to_string(), 0.0005)].into_iter().collect();
+    cfg.paper_balances = Some(balances);
+    let (pairs, tp, profit) = eth_opportunity();
+
+    let mut ex = PaperExecutor::new(&cfg);
+    assert!(matches!(ex.execute(&tp, &profit, [&pairs[0], &pairs[1], &pairs[2]]), Outcome::Skipped));
+    assert_eq!(ex.balance("BTC"), 0.0005);
+    assert_eq!(ex.executions(), 0);
+  }
+
+  #[test]
+  fn run_executes_once_per_opportunity_test() {
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let mut ta = TriAngleArb::with_pairs(&cfg, eth_pairs());
+    let mut ex = PaperExecutor::new(&cfg);
+    // 前三个 ticker 用于初始化, 之后同一个机会持续 3 个 ticker
+    ta.run(&mut VecSource {
+      tickers: vec![
+        ("ETHBTC", [0.05, 10.0], [0.0499, 10.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("BTCUSDT", [40000.0, 5.0], [39999.0, 5.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+      ],
+    }, Some(&mut ex));
+    assert_eq!(ex.executions(), 1);
+  }
+
+  // 第一次跳过, 之后提交, 记录调用次数
+  struct SkipOnce {
+    calls: usize,
+  }
+
+  impl Executor for SkipOnce {
+    fn execute(&mut self, _tp: &TriPair, _profit: &Profit, _pairs: [&TradingPair; 3]) -> Outcome {
+      self.calls += 1;
+      if self.calls == 1 { Outcome::Skipped } else { Outcome::Submitted }
+    }
+  }
+
+  #[test]
+  fn run_retries_skipped_opportunity_test() {
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let mut ta = TriAngleArb::with_pairs(&cfg, eth_pairs());
+    let mut ex = SkipOnce { calls: 0 };
+    // 第一次未执行的机会在下一个 ticker 再次执行, 提交后不再执行
+    ta.run(&mut VecSource {
+      tickers: vec![
+        ("ETHBTC", [0.05, 10.0], [0.0499, 10.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("BTCUSDT", [40000.0, 5.0], [39999.0, 5.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+      ],
+    }, Some(&mut ex));
+    assert_eq!(ex.calls, 2);
+  }
+
+  #[test]
+  fn run_zero_threshold_without_opportunity_test() {
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.trading_profit_threshold = 0.0;
+    let mut ta = TriAngleArb::with_pairs(&cfg, eth_pairs());
+    let mut ex = PaperExecutor::new(&cfg);
+    // 没有收益为正的三角组合时不执行
+    ta.run(&mut VecSource {
+      tickers: vec![
+        ("ETHBTC", [0.05, 10.0], [0.0499, 10.0]),
+        ("ETHUSDT", [1999.0, 10.0], [1998.0, 10.0]),
+        ("BTCUSDT", [40000.0, 5.0], [39999.0, 5.0]),
+        ("ETHUSDT", [1999.0, 10.0], [1998.0, 10.0]),
+      ],
+    }, Some(&mut ex));
+    assert_eq!(ex.executions(), 0);
+  }
+}
@@ -0,0 +1,54 @@
+mod common;
+
+#[cfg(test)]
+mod recorder_tests {
+  use std::collections::HashMap;
+  use triangle::config::Configuration;
+  use triangle::market_data::MarketDataSource;
+  use triangle::recorder::{TickerRecorder, TickerReplay};
+  use triangle::trading_pair::{PairFilters, TradingPair};
+  use triangle::tri_pair::Ticker;
+  use crate::common::VecSource;
+
+  #[test]
+  fn record_replay_test() {
+    let path = std::env::temp_dir().join(format!("triangle-record-{}.log", std::process::id()));
+    let pairs = vec![
+      TradingPair::new(1, "ETHBTC".to_string(), "ETH".to_string(), "BTC".to_string(), 0.001, 0.999)
+        .with_filters(PairFilters { step: 0.001, min_qty: 0.001, max_qty: 100000.0, min_notional: 0.0001 }),
+      TradingPair::new(2, "BTCUSDT".to_string(), "BTC".to_string(), "USDT".to_string(), 0.00001, 0.999),
+    ];
+    let symbol_id: HashMap<String, u32> = pairs.iter().map(|x| (x.get_symbol(), x.get_symbol_idx())).collect();
+    let source = VecSource {
+      tickers: vec![
+        ("ETHBTC", [0.05, 10.0], [0.0499, 8.0]),
+        ("BTCUSDT", [40000.5, 1.5], [40000.0, 2.25]),
+      ],
+    };
+
+    let mut recorder = TickerRecorder::new(source, &path, pairs).unwrap();
+    let forwarded: Vec<Ticker> = recorder.subscribe(symbol_id.clone()).iter().collect();
+    assert_eq!(forwarded.len(), 2);
+
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let mut replay = TickerReplay::open(&path, 0.0).unwrap();
+    let replay_pairs = replay.pairs(&cfg);
+    assert_eq!(replay_pairs.len(), 2);
+    assert_eq!(replay_pairs[0].get_symbol(), "ETHBTC");
+    assert_eq!(replay_pairs[1].text(), "BTC/USDT");
+    assert_eq!(replay_pairs[1].get_step(), 0.00001);
+    assert_eq!(replay_pairs[0].filters.min_notional, 0.0001);
+    assert_eq!(replay_pairs[0].filters.max_qty, 100000.0);
+
+    let replayed: Vec<Ticker> = replay.subscribe(symbol_id).iter().collect();
+    assert_eq!(replayed.len(), 2);
+    assert_eq!(replayed[0].idx, 1);
+    assert_eq!(replayed[0].ba, [0.05, 10.0]);
+    assert_eq!(replayed[0].bb, [0.0499, 8.0]);
+    assert_eq!(replayed[1].idx, 2);
+    assert_eq!(replayed[1].ba, [40000.5, 1.5]);
+    assert_eq!(replayed[1].bb, [40000.0, 2.25]);
+
+    std::fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod report_currency_tests {
+  use triangle::config::Configuration;
+  use triangle::trading_pair::TradingPair;
+  use triangle::tri_pair::Ticker;
+  use triangle::triangle::TriAngleArb;
+
+  fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+  }
+
+  fn arb() -> TriAngleArb {
+    arb_in("USDT")
+  }
+
+  fn arb_in(report_currency: &str) -> TriAngleArb {
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    // 不按投资范围计算, 收益以各自的起始资产计
+    cfg.investment_max = 0.0;
+    cfg.report_currency = Some(report_currency.to_string());
+    let pair = |idx: u32, base: &str, quote: &str, step: f64| {
+      TradingPair::new(idx, format!("{}{}", base, quote), base.to_string(), quote.to_string(), step, 1.0)
+    };
+    let pairs = vec![
+      pair(1, "ETH", "BTC", 0.001),
+      pair(2, "ETH", "USDT", 0.001),
+      pair(3, "BTC", "USDT", 0.00001),
+      pair(4, "XRP", "BTC", 1.0),
+      pair(5, "XRP", "USDT", 1.0),
+    ];
+    let mut ta = TriAngleArb::with_pairs(&cfg, pairs);
+    ta.set_ticker(Ticker { idx: 1, ba: [0.05, 0.1], bb: [0.0499, 0.1], ..Ticker::default() });
+    ta.set_ticker(Ticker { idx: 2, ba: [2100.5, 0.1], bb: [2100.0, 0.1], ..Ticker::default() });
+    ta.set_ticker(Ticker { idx: 3, ba: [40000.0, 5.0], bb: [39999.0, 5.0], ..Ticker::default() });
+    ta.set_ticker(Ticker { idx: 4, ba: [0.0000127, 100.0], bb: [0.0000126, 100.0], ..Ticker::default() });
+    ta.set_ticker(Ticker { idx: 5, ba: [0.5, 100.0], bb: [0.499, 100.0], ..Ticker::default() });
+    ta
+  }
+
+  #[test]
+  fn best_by_report_value_test() {
+    let mut ta = arb();
+    assert_eq!(ta.report_currency(), "USDT");
+    let angles = ta.angles(3).unwrap().clone();
+    let profits = ta.on_ticker_all(&angles);
+    assert_eq!(profits.len(), 2);
+
+    let eth = profits.iter().find(|p| p.name.starts_with("ETH")).unwrap();
+    let xrp = profits.iter().find(|p| p.name.starts_with("XRP")).unwrap();
+    // ETH 正向以 BTC 计: 0.005 BTC 买 0.1 ETH, 卖出 210 USDT, 买回 0.00525 BTC
+    assert_eq!(eth.asset, "BTC");
+    assert!(close(eth.profit, 0.00025));
+    assert!(close(eth.value, 0.00025 * 39999.0));
+    // XRP 反向以 USDT 计: 50 USDT 买 100 XRP, 卖出 0.00126 BTC, 换回 50.39874 USDT
+    assert!(xrp.rev);
+    assert_eq!(xrp.asset, "USDT");
+    assert!(close(xrp.profit, 0.39874));
+    assert!(close(xrp.value, 0.39874));
+
+    // 原始数值 XRP 更大, 换算后 ETH 更好
+    assert!(xrp.profit > eth.profit);
+    assert_eq!(ta.on_ticker(&angles).name, eth.name);
+  }
+
+  #[test]
+  fn best_by_ratio_without_value_test() {
+    // 没有 BNB 交易对, 收益无法换算, 按 ratio 选择而不是当作没有收益
+    let mut ta = arb_in("BNB");
+    let angles = ta.angles(3).unwrap().clone();
+    let profits = ta.on_ticker_all(&angles);
+    assert!(profits.iter().all(|p| p.profit > 0.0 && p.value == 0.0 && !p.has_value()));
+    let best = ta.on_ticker(&angles);
+    assert!(best.name.starts_with("ETH"));
+    assert!(close(best.ratio, 0.05));
+    let top = ta.top_profits(2);
+    assert_eq!(top.len(), 2);
+    assert_eq!(top[0].name, best.name);
+  }
+
+  #[test]
+  fn convert_test() {
+    let ta = arb();
+    assert_eq!(ta.convert(2.0, "BTC", "BTC"), Some(2.0));
+    assert!(close(ta.convert(1.0, "BTC", "USDT").unwrap(), 39999.0));
+    assert!(close(ta.convert(40000.0, "USDT", "BTC").unwrap(), 1.0));
+    // 没有直接的交易对, 经过 BTC 换算
+    assert!(close(ta.convert(1000.0, "XRP", "ETH").unwrap(), 1000.0 * 0.0000126 / 0.05));
+    assert_eq!(ta.convert(1.0, "XRP", "BNB"), None);
+  }
+}
@@ -0,0 +1,54 @@
+mod common;
+
+#[cfg(test)]
+mod sizing_tests {
+  use triangle::config::Configuration;
+  use triangle::tri_pair::Ticker;
+  use triangle::triangle::TriAngleArb;
+  use crate::common::eth_pairs;
+
+  fn arb(base: &str, min: f64, max: f64, step: f64) -> TriAngleArb {
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.investment_base = base.to_string();
+    cfg.investment_min = min;
+    cfg.investment_max = max;
+    cfg.investment_step = step;
+    let pairs = eth_pairs();
+    let mut ta = TriAngleArb::with_pairs(&cfg, pairs);
+    ta.set_ticker(Ticker { idx: 1, ba: [0.05, 10.0], bb: [0.0499, 10.0], ..Ticker::default() });
+    ta.set_ticker(Ticker { idx: 2, ba: [2100.5, 10.0], bb: [2100.0, 10.0], ..Ticker::default() });
+    ta.set_ticker(Ticker { idx: 3, ba: [40000.0, 5.0], bb: [39999.0, 5.0], ..Ticker::default() });
+    ta
+  }
+
+  #[test]
+  fn size_in_investment_base_test() {
+    let mut ta = arb("USDT", 10.0, 100.0, 10.0);
+    let angles = ta.angles(1).unwrap().clone();
+    let profit = ta.on_ticker(&angles);
+
+    // 从 BTC 发起, 100 USDT 按 40000 换算为 0.0025 BTC 买入 0.05 ETH, 最终收回 0.00255744 BTC
+    // 投入和收益按买一价 39999 换算回 USDT
+    assert!(!profit.rev);
+    assert!((profit.amount - 0.05).abs() < 1e-12);
+    assert!((profit.invest - 0.0025 * 39999.0).abs() < 1e-9);
+    assert!((profit.profit - 0.00005744 * 39999.0).abs() < 1e-9);
+    assert!((profit.ratio - profit.profit / profit.invest).abs() < 1e-12);
+  }
+
+  #[test]
+  fn no_profitable_size_test() {
+    // 投入过小时按 step 取整的损失大于价差, 该范围内没有机会
+    let mut ta = arb("USDT", 10.0, 40.0, 10.0);
+    let angles = ta.angles(1).unwrap().clone();
+    let profit = ta.on_ticker_all(&angles).remove(0);
+    assert_eq!(profit.profit, 0.0);
+    assert_eq!(profit.ratio, 0.0);
+
+    // investment_base 不在三角组合中时保持按盘口数量计算的结果
+    let mut ta = arb("BNB", 10.0, 40.0, 10.0);
+    let profit = ta.on_ticker_all(&angles).remove(0);
+    assert_eq!(profit.invest, 0.0);
+    assert!(profit.profit > 0.0);
+  }
+}
@@ -0,0 +1,113 @@
+mod common;
+
+#[cfg(test)]
+mod sqlite_tests {
+  use std::sync::{Arc, Mutex};
+  use triangle::config::Configuration;
+  use triangle::sink::OpportunitySink;
+  use triangle::sqlite::SqliteSink;
+  use triangle::tri_pair::Profit;
+  use triangle::triangle::TriAngleArb;
+  use crate::common::{VecSource, eth_pairs};
+
+  fn profit(name: &str, ts: u64, ratio: f64) -> Profit {
+    Profit {
+      name: name.to_string(),
+      ratio,
+      amount: 1.0,
+      profit: ratio,
+      asset: "USDT".to_string(),
+      value: ratio,
+      ts,
+      ..Profit::default()
+    }
+  }
+
+  #[test]
+  fn record_and_query_test() {
+    let path = std::env::temp_dir().join(format!("triangle-opportunities-{}.db", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = std::fs::remove_file(path);
+    let mut sink = SqliteSink::open(path).unwrap();
+    let symbols = ["ETHBTC", "ETHUSDT", "BTCUSDT"];
+    sink.record(&profit("A", 1000, 0.002), symbols).unwrap();
+    sink.record(&profit("A", 2000, 0.004), symbols).unwrap();
+    sink.record(&profit("B", 3000, 0.001), symbols).unwrap();
+    assert_eq!(sink.count().unwrap(), 3);
+
+    let stats = sink.triangle_stats(0).unwrap();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0].name, "A");
+    assert_eq!(stats[0].count, 2);
+    assert!((stats[0].avg_ratio - 0.003).abs() < 1e-12);
+    assert_eq!(stats[0].max_ratio, 0.004);
+    let stats = sink.triangle_stats(2000).unwrap();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0].count, 1);
+
+    // 重新打开时保留已有数据
+    drop(sink);
+    let sink = SqliteSink::open(path).unwrap();
+    assert_eq!(sink.count().unwrap(), 3);
+    drop(sink);
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn wal_mode_test() {
+    let path = std::env::temp_dir().join(format!("triangle-wal-{}.db", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = std::fs::remove_file(path);
+    let mut sink = SqliteSink::open(path).unwrap();
+    assert_eq!(sink.journal_mode().unwrap(), "wal");
+    sink.record(&profit("ETH-BTC-USDT", 1, 0.001), ["ETHBTC", "ETHUSDT", "BTCUSDT"]).unwrap();
+    // 重新打开时读到 WAL 中已提交的数据
+    assert_eq!(SqliteSink::open(path).unwrap().count().unwrap(), 1);
+    drop(sink);
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn scan_records_opened_opportunities_test() {
+    let path = std::env::temp_dir().join(format!("triangle-scan-{}.db", std::process::id()));
+    let path = path.to_str().unwrap();
+    let _ = std::fs::remove_file(path);
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let pairs = eth_pairs();
+    let mut ta = TriAngleArb::with_pairs(&cfg, pairs);
+    ta.add_sink(Arc::new(Mutex::new(SqliteSink::open(path).unwrap())));
+    ta.start_with(&mut VecSource {
+      tickers: vec![
+        ("ETHBTC", [0.05, 10.0], [0.0499, 10.0]),
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("BTCUSDT", [40000.0, 5.0], [39999.0, 5.0]),
+        // 同一个机会持续, 只写入一次
+        ("ETHUSDT", [2100.5, 10.0], [2100.0, 10.0]),
+        ("ETHUSDT", [2101.0, 10.0], [2100.5, 10.0]),
+      ],
+    }).unwrap();
+
+    let sink = SqliteSink::open(path).unwrap();
+    assert_eq!(sink.count().unwrap(), 1);
+    let stats = sink.triangle_stats(0).unwrap();
+    assert_eq!(stats[0].count, 1);
+    assert!(stats[0].max_ratio > 0.0);
+    // 关闭最后一个连接时 WAL 文件合并后删除
+    drop((sink, ta));
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn sink_error_stops_start_test() {
+    let dir = std::env::temp_dir().join(format!("triangle-sink-{}", std::process::id()));
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.sqlite_path = Some("/nonexistent/triangle/opportunities.db".to_string());
+    cfg.export_dir = Some(dir.to_str().unwrap().to_string());
+    let mut ta = TriAngleArb::with_pairs(&cfg, eth_pairs());
+    // export 仍然单独创建, 但 sqlite 失败时不启动扫描
+    let err = ta.start_with(&mut VecSource { tickers: vec![] }).unwrap_err();
+    assert!(err.to_string().starts_with("storage error: sqlite_path /nonexistent/triangle/opportunities.db"));
+    assert!(!err.to_string().contains("export_dir"));
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+}
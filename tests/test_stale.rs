@@ -0,0 +1,101 @@
+mod common;
+
+#[cfg(test)]
+mod stale_tests {
+  use std::collections::HashMap;
+  use std::sync::mpsc::{self, Receiver};
+  use chrono::prelude::Utc;
+  use triangle::config::Configuration;
+  use triangle::market_data::MarketDataSource;
+  use triangle::paper::PaperExecutor;
+  use triangle::tri_pair::Ticker;
+  use triangle::triangle::TriAngleArb;
+  use crate::common::eth_pairs;
+
+  fn arb() -> TriAngleArb {
+    let mut cfg = Configuration::new("config/sample_config.json").unwrap();
+    cfg.trading_age_threshold = 100;
+    let pairs = eth_pairs();
+    TriAngleArb::with_pairs(&cfg, pairs)
+  }
+
+  fn set_tickers(ta: &mut TriAngleArb, ts: [i64; 3]) {
+    ta.set_ticker(Ticker { idx: 1, ba: [0.05, 10.0], bb: [0.0499, 10.0], update_id: 1, ts: ts[0] });
+    ta.set_ticker(Ticker { idx: 2, ba: [2100.5, 10.0], bb: [2100.0, 10.0], update_id: 1, ts: ts[1] });
+    ta.set_ticker(Ticker { idx: 3, ba: [40000.0, 5.0], bb: [39999.0, 5.0], update_id: 1, ts: ts[2] });
+  }
+
+  #[test]
+  fn stale_leg_test() {
+    let now = Utc::now().timestamp_millis();
+    let mut ta = arb();
+    set_tickers(&mut ta, [now, now, now]);
+    assert!(ta.on_symbol(2).profit > 0.0);
+    assert_eq!(ta.stale_count(), 0);
+
+    // BTC/USDT 1 秒未更新, 超过 100ms 的阈值
+    let mut ta = arb();
+    set_tickers(&mut ta, [now, now, now - 1000]);
+    assert_eq!(ta.on_symbol(2).profit, 0.0);
+    assert_eq!(ta.stale_count(), 1);
+    let all = ta.on_symbol_all(2);
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0].name, "");
+    assert_eq!(ta.stale_count(), 2);
+
+    // 接收时间未知的 ticker 不检查
+    let mut ta = arb();
+    set_tickers(&mut ta, [now, now, 0]);
+    assert!(ta.on_symbol(2).profit > 0.0);
+    assert_eq!(ta.stale_count(), 0);
+  }
+
+  // 按顺序发送带接收时间的 ticker 后断开队列
+  struct TsSource {
+    tickers: Vec<Ticker>,
+  }
+
+  impl MarketDataSource for TsSource {
+    fn subscribe(&mut self, _: HashMap<String, u32>) -> Receiver<Ticker> {
+      let (tx, rx) = mpsc::channel();
+      for t in self.tickers.drain(..) {
+        tx.send(t).unwrap();
+      }
+      rx
+    }
+  }
+
+  #[test]
+  fn stale_keeps_opportunity_test() {
+    let now = Utc::now().timestamp_millis();
+    let cfg = Configuration::new("config/sample_config.json").unwrap();
+    let mut ta = arb();
+    let mut ex = PaperExecutor::new(&cfg);
+    let eth_usdt = |ts: i64| Ticker { idx: 2, ba: [2100.5, 10.0], bb: [2100.0, 10.0], update_id: 1, ts };
+    let btc_usdt = |update_id: u64, ts: i64| Ticker { idx: 3, ba: [40000.0, 5.0], bb: [39999.0, 5.0], update_id, ts };
+    // 前三个 ticker 用于初始化, 机会出现后 BTC/USDT 过期一段时间再恢复, 期间的计算被跳过, 机会不结束, 不会再次执行
+    ta.run(&mut TsSource {
+      tickers: vec![
+        Ticker { idx: 1, ba: [0.05, 10.0], bb: [0.0499, 10.0], update_id: 1, ts: now },
+        eth_usdt(now),
+        btc_usdt(1, now),
+        eth_usdt(now),
+        btc_usdt(2, now - 1000),
+        eth_usdt(now),
+        btc_usdt(3, now),
+      ],
+    }, Some(&mut ex));
+    assert!(ta.stale_count() > 0);
+    assert_eq!(ex.executions(), 1);
+  }
+
+  #[test]
+  fn out_of_order_update_test() {
+    let mut ta = arb();
+    ta.set_ticker(Ticker { idx: 1, ba: [0.05, 10.0], bb: [0.0499, 10.0], update_id: 10, ts: 0 });
+    ta.set_ticker(Ticker { idx: 1, ba: [0.06, 10.0], bb: [0.0599, 10.0], update_id: 9, ts: 0 });
+    assert_eq!(ta.ticker(1).unwrap().ba, [0.05, 10.0]);
+    ta.set_ticker(Ticker { idx: 1, ba: [0.07, 10.0], bb: [0.0699, 10.0], update_id: 11, ts: 0 });
+    assert_eq!(ta.ticker(1).unwrap().ba, [0.07, 10.0]);
+  }
+}
@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tracker_tests {
+  use triangle::tracker::OpportunityTracker;
+  use triangle::tri_pair::Profit;
+
+  fn profit(rev: bool, ratio: f64) -> Profit {
+    Profit {
+      name: "ETH-BTC-USDT".to_string(),
+      rev,
+      ratio,
+      profit: ratio * 0.1,
+      asset: "BTC".to_string(),
+      value: ratio * 4000.0,
+      ..Profit::default()
+    }
+  }
+
+  #[test]
+  fn lifecycle_test() {
+    let name = "ETH-BTC-USDT";
+    let mut tracker = OpportunityTracker::new(0.001);
+
+    assert!(!tracker.update(name, &profit(false, 0.0005), 1000).opened);
+    assert!(tracker.update(name, &profit(false, 0.002), 1100).opened);
+    // 持续期间不重复开启, 只更新峰值
+    let up = tracker.update(name, &profit(false, 0.005), 1200);
+    assert!(!up.opened && up.closed.is_empty());
+    assert!(tracker.update(name, &profit(false, 0.003), 1300).closed.is_empty());
+    assert!(tracker.is_open(name, false));
+
+    let up = tracker.update(name, &profit(false, 0.0), 1500);
+    assert_eq!(up.closed.len(), 1);
+    let op = &up.closed[0];
+    assert_eq!(op.opened_ms, 1100);
+    assert_eq!(op.closed_ms, 1500);
+    assert_eq!(op.duration_ms(), 400);
+    assert_eq!(op.ticks, 3);
+    assert_eq!(op.peak_ratio, 0.005);
+    assert_eq!(op.peak_value, 20.0);
+    assert_eq!(tracker.open_count(), 0);
+  }
+
+  #[test]
+  fn direction_flip_test() {
+    let name = "ETH-BTC-USDT";
+    let mut tracker = OpportunityTracker::new(0.001);
+    tracker.update(name, &profit(false, 0.002), 1000);
+    // 方向翻转: 结束正向, 开启反向
+    let up = tracker.update(name, &profit(true, 0.002), 1200);
+    assert!(up.opened);
+    assert_eq!(up.closed.len(), 1);
+    assert!(!up.closed[0].rev);
+    assert_eq!(up.closed[0].duration_ms(), 200);
+
+    tracker.update(name, &profit(true, 0.003), 1500);
+    // 结束跟踪时以最后一次出现的时间结束
+    let ops = tracker.finish();
+    assert_eq!(ops.len(), 1);
+    assert!(ops[0].rev);
+    assert_eq!(ops[0].duration_ms(), 300);
+  }
+
+  #[test]
+  fn mark_executed_test() {
+    let name = "ETH-BTC-USDT";
+    let mut tracker = OpportunityTracker::new(0.001);
+    tracker.update(name, &profit(false, 0.002), 1000);
+    assert!(!tracker.get(name, false).unwrap().executed);
+    tracker.mark_executed(name, false);
+    // 机会持续期间保持已执行
+    tracker.update(name, &profit(false, 0.003), 1100);
+    assert!(tracker.get(name, false).unwrap().executed);
+    // 机会结束后重新开启, 可以再次执行
+    tracker.update(name, &profit(false, 0.0), 1200);
+    tracker.update(name, &profit(false, 0.002), 1300);
+    assert!(!tracker.get(name, false).unwrap().executed);
+    assert!(tracker.get(name, true).is_none());
+  }
+
+  #[test]
+  fn mark_notified_test() {
+    let name = "ETH-BTC-USDT";
+    let mut tracker = OpportunityTracker::new(0.001);
+    tracker.update(name, &profit(false, 0.002), 1000);
+    tracker.mark_notified(name, false);
+    tracker.update(name, &profit(false, 0.003), 1100);
+    assert!(tracker.get(name, false).unwrap().notified);
+    // 机会结束后重新开启, 可以再次通知
+    tracker.update(name, &profit(false, 0.0), 1200);
+    tracker.update(name, &profit(false, 0.002), 1300);
+    assert!(!tracker.get(name, false).unwrap().notified);
+  }
+}
@@ -9,6 +9,7 @@ mod trading_pair_tests {
       "BNB".to_string(),
       "BTC".to_string(),
       0.01,
+      0.999,
     );
     assert_eq!(tp.get_symbol(), "BNBBTC");
     assert_eq!(tp.get_step(), 0.01f64);
@@ -25,10 +26,11 @@ mod trading_pair_tests {
       "BNB".to_string(),
       "BTC".to_string(),
       0.01,
+      0.999,
     );
-    assert_eq!(tp1.has_asset("BNB".to_string()), true);
-    assert_eq!(tp1.has_asset("BTC".to_string()), true);
-    assert_eq!(tp1.has_asset("ETH".to_string()), false);
+    assert!(tp1.has_asset("BNB".to_string()));
+    assert!(tp1.has_asset("BTC".to_string()));
+    assert!(!tp1.has_asset("ETH".to_string()));
     assert_eq!(tp1.get_the_other("BNB".to_string()), "BTC");
     assert_eq!(tp1.get_the_other("BTC".to_string()), "BNB");
   }
@@ -42,6 +44,7 @@ mod trading_pair_tests {
       "BNB".to_string(),
       "BTC".to_string(),
       0.01,
+      0.999,
     );
     let tp2: TradingPair = TradingPair::new(
       0,
@@ -49,6 +52,7 @@ mod trading_pair_tests {
       "BTC".to_string(),
       "BNB".to_string(),
       0.00001,
+      0.999,
     );
     assert_eq!(tp1, tp2);
   }
@@ -62,6 +66,7 @@ mod trading_pair_tests {
       "BNB".to_string(),
       "BTC".to_string(),
       0.01,
+      0.999,
     );
     assert_eq!(tp1.text(), "BNB/BTC");
   }